@@ -25,8 +25,9 @@ echo "[1/4] Installing applet and scripts..."
 chmod +x "$BASE_DIR/bin/"*
 killall cosmic-applet-night-light || true
 cp "$BASE_DIR/bin/cosmic-applet-night-light" /usr/local/bin/
+cp "$BASE_DIR/bin/night-light-ctl" /usr/local/bin/
+# the old command, now calls night-light-ctl
 cp "$BASE_DIR/bin/toggle-night-mode" /usr/local/bin/
-chmod +x /usr/local/bin/toggle-night-mode
 
 # 2. Patch Compositor
 echo "[2/4] Patching COSMIC compositor..."
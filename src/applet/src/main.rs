@@ -3,68 +3,323 @@ use cosmic::iced::window::Id;
 use cosmic::iced::{Length, Rectangle, Alignment};
 use cosmic::iced_runtime::core::window;
 use cosmic::surface::action::{app_popup, destroy_popup};
-use cosmic::widget::{list_column, settings, toggler, button};
+use cosmic::widget::{button, dropdown, list_column, settings, slider, text, toggler};
 use cosmic::iced::widget::row;
 use cosmic::Element;
-use zbus::blocking::Connection;
-use std::time::{Duration, Instant};
-use chrono::{Local, Timelike};
-
-#[zbus::proxy(
-    interface = "io.github.kernel_ux.EliteNightLight.Manager",
-    default_service = "io.github.kernel_ux.EliteNightLight",
-    default_path = "/io/github/kernel_ux/EliteNightLight"
-)]
-trait NightLight {
-    #[zbus(name = "Enabled")]
-    fn enabled(&self) -> zbus::Result<bool>;
-    #[zbus(name = "Level")]
-    fn level(&self) -> zbus::Result<u8>;
-    #[zbus(name = "SetEnabled")]
-    fn set_enabled(&self, enabled: bool) -> zbus::Result<()>;
-    #[zbus(name = "SetLevel")]
-    fn set_level(&self, level: u8) -> zbus::Result<()>;
+use cosmic::iced::futures::channel::mpsc::Sender;
+use futures_util::{stream, SinkExt, Stream, StreamExt};
+use night_light_proxy::{NightLightProxy, PATH, SERVICE};
+use std::future::Future;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use zbus::fdo::PropertiesProxy;
+use zbus::proxy::CacheProperties;
+
+const MIN_TEMPERATURE: u32 = 1000;
+const NEUTRAL_TEMPERATURE: u32 = 6500;
+const MINUTES_PER_DAY: i32 = 24 * 60;
+/// Minutes between the entries of the time pickers' minute dropdown
+const SCHEDULE_STEP: u16 = 5;
+/// Least time between two temperatures sent while the slider is dragged
+const PREVIEW_INTERVAL: Duration = Duration::from_millis(100);
+/// First wait before connecting to the session bus again, doubled after every failure
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+static HOURS: [&str; 24] = [
+    "00", "01", "02", "03", "04", "05", "06", "07", "08", "09", "10", "11", "12", "13", "14", "15",
+    "16", "17", "18", "19", "20", "21", "22", "23",
+];
+static MINUTES: [&str; 12] = [
+    "00", "05", "10", "15", "20", "25", "30", "35", "40", "45", "50", "55",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleMode {
+    Disabled,
+    Custom,
+    Solar,
 }
 
-const ID: &str = "io.github.kernel_ux.EliteNightLight";
+impl ScheduleMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleMode::Disabled => "disabled",
+            ScheduleMode::Custom => "custom",
+            ScheduleMode::Solar => "solar",
+        }
+    }
 
-pub struct Window {
-    core: Core,
-    popup: Option<Id>,
+    fn parse(mode: &str) -> Self {
+        match mode {
+            "custom" => ScheduleMode::Custom,
+            "solar" => ScheduleMode::Solar,
+            _ => ScheduleMode::Disabled,
+        }
+    }
+}
+
+/// Which end of the custom schedule a time picker changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    From,
+    To,
+}
+
+/// Everything the popup shows, as reported by the compositor
+#[derive(Clone, Debug)]
+pub struct NightLightStatus {
     enabled: bool,
     level: u8,
-    auto: bool,
-    manual_override: bool,
-    last_change: Instant,
+    temperature: u32,
+    /// Temperatures of the levels 1 to 3, as the compositor defines them
+    level_temperatures: Vec<u32>,
+    schedule_mode: ScheduleMode,
+    schedule_from: u16,
+    schedule_to: u16,
+    schedule_ramp: u16,
+    has_location: bool,
+    inhibited: bool,
+    /// What the schedule asks for, `None` without an active schedule
+    scheduled: Option<bool>,
+    /// Whether a timed override keeps night light on or off
+    override_enabled: Option<bool>,
+    /// Unix timestamp the override ends, `0` if it lasts until the next transition
+    override_expiry: i64,
 }
 
-impl Default for Window {
+impl Default for NightLightStatus {
     fn default() -> Self {
         Self {
-            core: Core::default(),
-            popup: None,
             enabled: false,
-            level: 1,
-            auto: false,
-            manual_override: false,
-            last_change: Instant::now() - Duration::from_secs(10),
+            level: 0,
+            temperature: NEUTRAL_TEMPERATURE,
+            level_temperatures: Vec::new(),
+            schedule_mode: ScheduleMode::Disabled,
+            schedule_from: 19 * 60,
+            schedule_to: 7 * 60,
+            schedule_ramp: 0,
+            has_location: false,
+            inhibited: false,
+            scheduled: None,
+            override_enabled: None,
+            override_expiry: 0,
         }
     }
 }
 
+/// Reads the full state, `None` if the compositor isn't on the bus.
+async fn fetch_status(proxy: &NightLightProxy<'_>) -> Option<NightLightStatus> {
+    let defaults = NightLightStatus::default();
+    Some(NightLightStatus {
+        enabled: proxy.enabled().await.ok()?,
+        level: proxy.level().await.unwrap_or(defaults.level),
+        temperature: proxy.temperature().await.unwrap_or(defaults.temperature),
+        level_temperatures: proxy
+            .level_temperatures()
+            .await
+            .unwrap_or(defaults.level_temperatures),
+        schedule_mode: proxy
+            .schedule_mode()
+            .await
+            .map_or(defaults.schedule_mode, |mode| ScheduleMode::parse(&mode)),
+        schedule_from: proxy.schedule_from().await.unwrap_or(defaults.schedule_from),
+        schedule_to: proxy.schedule_to().await.unwrap_or(defaults.schedule_to),
+        schedule_ramp: proxy.schedule_ramp().await.unwrap_or(defaults.schedule_ramp),
+        has_location: proxy.location().await.is_ok(),
+        inhibited: proxy.inhibited().await.unwrap_or(defaults.inhibited),
+        scheduled: match proxy.scheduled().await.as_deref() {
+            Ok("enabled") => Some(true),
+            Ok("disabled") => Some(false),
+            _ => None,
+        },
+        override_enabled: match proxy.override_().await.as_deref() {
+            Ok("enabled") => Some(true),
+            Ok("disabled") => Some(false),
+            _ => None,
+        },
+        override_expiry: proxy
+            .override_expiry()
+            .await
+            .unwrap_or(defaults.override_expiry),
+    })
+}
+
+/// Follows the compositor over one session bus connection: sends its state whenever a property
+/// changes, and again whenever cosmic-comp comes back, so a restart doesn't leave us stale.
+async fn follow(output: &mut Sender<Message>) -> zbus::Result<()> {
+    let conn = zbus::Connection::session().await?;
+    // every change brings a fresh read anyway, a cache would only lag behind the signals
+    let proxy = NightLightProxy::builder(&conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let properties = PropertiesProxy::builder(&conn)
+        .destination(SERVICE)?
+        .path(PATH)?
+        .build()
+        .await?;
+    let owner_changes = proxy.inner().receive_owner_changed().await?;
+    let property_changes = properties.receive_properties_changed().await?;
+    let _ = output.send(Message::Connected(proxy.clone())).await;
+
+    // `Some(false)` when cosmic-comp left the bus, `None` for a property change
+    let mut events = stream::select(
+        owner_changes.map(|owner| Some(owner.is_some())),
+        property_changes.map(|_| None),
+    );
+    let mut available = true;
+    loop {
+        let message = if available {
+            match fetch_status(&proxy).await {
+                Some(status) => Message::UpdateState(status),
+                None => Message::Disconnected,
+            }
+        } else {
+            Message::Disconnected
+        };
+        let _ = output.send(message).await;
+
+        match events.next().await {
+            Some(Some(owned)) => available = owned,
+            Some(None) => available = true,
+            None => return Ok(()),
+        }
+    }
+}
+
+fn night_light_events() -> impl Stream<Item = Message> {
+    cosmic::iced::stream::channel(16, |mut output: Sender<Message>| async move {
+        let mut delay = RETRY_DELAY;
+        loop {
+            match follow(&mut output).await {
+                // we were connected until the bus went away, try again soon
+                Ok(()) => delay = RETRY_DELAY,
+                Err(err) => log::warn!("Night light D-Bus connection failed: {err}"),
+            }
+            let _ = output.send(Message::Disconnected).await;
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    })
+}
+
+fn format_minute(minute: u16) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+fn minutes_until(timestamp: i64) -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as i64);
+    // round up, so the last minute doesn't show as zero
+    (timestamp - now + 59).max(0) / 60
+}
+
+/// Tells what night light does now and when that changes, e.g. "On until 07:00".
+fn status_line(status: &NightLightStatus) -> String {
+    let state = if status.enabled { "On" } else { "Off" };
+    if status.inhibited {
+        return "Off while an app needs true colors".into();
+    }
+    if status.override_enabled.is_some() && status.override_expiry != 0 {
+        return format!(
+            "{state} for {} more minutes",
+            minutes_until(status.override_expiry)
+        );
+    }
+
+    if status.schedule_mode == ScheduleMode::Solar && !status.has_location {
+        return format!("{state}, the sunset schedule needs a location");
+    }
+    // a change by hand outlasts the next transition, only an override ends with it
+    let Some(night) = status
+        .scheduled
+        .filter(|night| *night == status.enabled || status.override_enabled.is_some())
+    else {
+        return state.into();
+    };
+
+    let until = match status.schedule_mode {
+        ScheduleMode::Disabled => return state.into(),
+        // night light fades out after the transition, it is on until that is done
+        ScheduleMode::Custom if night => {
+            let end = (status.schedule_to as i32 + status.schedule_ramp as i32) % MINUTES_PER_DAY;
+            format_minute(end as u16)
+        }
+        ScheduleMode::Custom => format_minute(status.schedule_from),
+        ScheduleMode::Solar if night => "sunrise".into(),
+        ScheduleMode::Solar => "sunset".into(),
+    };
+    format!("{state} until {until}")
+}
+
+const ID: &str = "io.github.kernel_ux.EliteNightLight";
+
+#[derive(Default)]
+pub struct Window {
+    core: Core,
+    popup: Option<Id>,
+    /// Talks to the compositor, `None` until the subscription connected
+    proxy: Option<NightLightProxy<'static>>,
+    /// `None` while the compositor isn't on the bus
+    status: Option<NightLightStatus>,
+    /// Temperature under the slider while it is dragged, shown instead of the compositor's
+    dragged_temperature: Option<u32>,
+    /// When the last preview of a drag was sent, to throttle the next one
+    preview_sent: Option<Instant>,
+}
+
 #[derive(Clone, Debug)]
 pub enum Message {
     PopupClosed(Id),
     ToggleEnabled(bool),
     SetLevel(u8),
-    ToggleAuto(bool),
-    CheckSchedule,
-    CheckState,
+    /// The slider moved, previewed at most every [`PREVIEW_INTERVAL`]
+    DragTemperature(u32),
+    /// The slider was released at the dragged temperature
+    SetTemperature,
+    SetScheduleMode(ScheduleMode),
+    /// Moves one end of the custom schedule to the given minute of the day
+    SetSchedule(Edge, u16),
+    Connected(NightLightProxy<'static>),
+    UpdateState(NightLightStatus),
+    /// cosmic-comp left the bus, or we never reached it
+    Disconnected,
+    /// Redraws countdowns in the status line
+    Tick,
     Surface(cosmic::surface::Action),
-    UpdateState(bool, u8),
     NoOp,
 }
 
+impl Window {
+    fn send_temperature(&self, kelvin: u32) -> Task<Message> {
+        self.call(move |proxy| async move {
+            proxy.set_enabled(true).await?;
+            proxy.set_temperature(kelvin).await
+        })
+    }
+
+    /// Runs `call` against the compositor in the background, the property change it causes
+    /// brings the popup in line with what the compositor actually did.
+    fn call<F, Fut>(&self, call: F) -> Task<Message>
+    where
+        F: FnOnce(NightLightProxy<'static>) -> Fut + Send + 'static,
+        Fut: Future<Output = zbus::Result<()>> + Send + 'static,
+    {
+        let Some(proxy) = self.proxy.clone() else {
+            return Task::none();
+        };
+        Task::perform(
+            async move {
+                if let Err(err) = call(proxy).await {
+                    log::warn!("Night light request failed: {err}");
+                }
+                Message::NoOp
+            },
+            |m| cosmic::Action::App(m),
+        )
+    }
+}
+
 impl cosmic::Application for Window {
     type Executor = cosmic::SingleThreadExecutor;
     type Flags = ();
@@ -84,19 +339,8 @@ impl cosmic::Application for Window {
             core,
             ..Default::default()
         };
-        
-        let task = Task::perform(async move {
-            if let Ok(conn) = Connection::session() {
-                if let Ok(proxy) = NightLightProxyBlocking::new(&conn) {
-                    let enabled = proxy.enabled().unwrap_or(false);
-                    let level = proxy.level().unwrap_or(1);
-                    return Message::UpdateState(enabled, level);
-                }
-            }
-            Message::NoOp
-        }, |m| cosmic::Action::App(m));
 
-        (window, task)
+        (window, Task::none())
     }
 
     fn on_close_requested(&self, id: window::Id) -> Option<Message> {
@@ -104,9 +348,18 @@ impl cosmic::Application for Window {
     }
 
     fn subscription(&self) -> cosmic::iced::Subscription<Self::Message> {
-        let schedule = cosmic::iced::time::every(Duration::from_secs(30)).map(|_| Message::CheckSchedule);
-        let sync = cosmic::iced::time::every(Duration::from_secs(1)).map(|_| Message::CheckState);
-        cosmic::iced::Subscription::batch(vec![schedule, sync])
+        let events = cosmic::iced::Subscription::run(night_light_events);
+        let counting_down = self.status.as_ref().is_some_and(|status| {
+            status.override_enabled.is_some() && status.override_expiry != 0
+        });
+        if counting_down {
+            cosmic::iced::Subscription::batch([
+                events,
+                cosmic::iced::time::every(Duration::from_secs(30)).map(|_| Message::Tick),
+            ])
+        } else {
+            events
+        }
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
@@ -117,104 +370,103 @@ impl cosmic::Application for Window {
                 }
             }
             Message::ToggleEnabled(toggled) => {
-                self.enabled = toggled;
-                self.last_change = Instant::now();
-                if self.auto {
-                    self.manual_override = true;
+                if let Some(status) = self.status.as_mut() {
+                    status.enabled = toggled;
                 }
-                return Task::perform(async move {
-                    if let Ok(conn) = Connection::session() {
-                        if let Ok(proxy) = NightLightProxyBlocking::new(&conn) {
-                            let _ = proxy.set_enabled(toggled);
-                        }
-                    }
-                    Message::NoOp
-                }, |m| cosmic::Action::App(m));
+                return self.call(move |proxy| async move { proxy.set_enabled(toggled).await });
             }
             Message::SetLevel(level) => {
-                self.level = level;
-                self.enabled = true;
-                self.last_change = Instant::now();
-                return Task::perform(async move {
-                    if let Ok(conn) = Connection::session() {
-                        if let Ok(proxy) = NightLightProxyBlocking::new(&conn) {
-                            let _ = proxy.set_enabled(true);
-                            let _ = proxy.set_level(level);
-                        }
+                if let Some(status) = self.status.as_mut() {
+                    status.level = level;
+                    if let Some(kelvin) = status.level_temperatures.get(level as usize - 1) {
+                        status.temperature = *kelvin;
                     }
-                    Message::NoOp
-                }, |m| cosmic::Action::App(m));
+                    status.enabled = true;
+                }
+                return self.call(move |proxy| async move {
+                    proxy.set_enabled(true).await?;
+                    proxy.set_level(level).await
+                });
             }
-            Message::ToggleAuto(auto) => {
-                self.auto = auto;
-                self.manual_override = false;
-                self.last_change = Instant::now();
-                if auto {
-                    return cosmic::task::message(cosmic::Action::App(Message::CheckSchedule));
+            Message::DragTemperature(kelvin) => {
+                self.dragged_temperature = Some(kelvin);
+                // the compositor fades to every value we send, which previews the slider live,
+                // but a request per pixel of movement would only queue up fades
+                if self
+                    .preview_sent
+                    .is_some_and(|sent| sent.elapsed() < PREVIEW_INTERVAL)
+                {
+                    return Task::none();
                 }
+                self.preview_sent = Some(Instant::now());
+                return self.send_temperature(kelvin);
             }
-            Message::CheckSchedule => {
-                if self.auto && !self.manual_override && self.last_change.elapsed() > Duration::from_secs(5) {
-                    let now = Local::now();
-                    let hour = now.hour();
-                    let should_be_enabled = hour >= 19 || hour < 7;
-                    
-                    if self.enabled != should_be_enabled {
-                        self.enabled = should_be_enabled;
-                        return Task::perform(async move {
-                            if let Ok(conn) = Connection::session() {
-                                if let Ok(proxy) = NightLightProxyBlocking::new(&conn) {
-                                    let _ = proxy.set_enabled(should_be_enabled);
-                                }
-                            }
-                            Message::NoOp
-                        }, |m| cosmic::Action::App(m));
-                    }
+            Message::SetTemperature => {
+                self.preview_sent = None;
+                let Some(kelvin) = self.dragged_temperature.take() else {
+                    return Task::none();
+                };
+                // show the value right away, the property change confirms it
+                if let Some(status) = self.status.as_mut() {
+                    status.temperature = kelvin;
+                    status.level = status
+                        .level_temperatures
+                        .iter()
+                        .position(|preset| *preset == kelvin)
+                        .map_or(0, |index| index as u8 + 1);
+                    status.enabled = true;
                 }
+                // where the slider stopped, the last preview might have been skipped
+                return self.send_temperature(kelvin);
             }
-            Message::CheckState => {
-                if self.last_change.elapsed() > Duration::from_secs(3) {
-                    return Task::perform(async move {
-                        if let Ok(conn) = Connection::session() {
-                            if let Ok(proxy) = NightLightProxyBlocking::new(&conn) {
-                                let enabled = proxy.enabled().unwrap_or(false);
-                                let level = proxy.level().unwrap_or(1);
-                                return Message::UpdateState(enabled, level);
-                            }
-                        }
-                        Message::NoOp
-                    }, |m| cosmic::Action::App(m));
+            Message::SetScheduleMode(mode) => {
+                if let Some(status) = self.status.as_mut() {
+                    status.schedule_mode = mode;
                 }
+                return self
+                    .call(move |proxy| async move { proxy.set_schedule_mode(mode.as_str()).await });
             }
-            Message::UpdateState(enabled, level) => {
-                if self.last_change.elapsed() > Duration::from_secs(3) {
-                    if self.auto && self.enabled != enabled {
-                        let now = Local::now();
-                        let hour = now.hour();
-                        let should_be_enabled = hour >= 19 || hour < 7;
-                        if enabled == should_be_enabled {
-                            self.manual_override = false;
-                        } else {
-                            self.manual_override = true;
-                        }
-                    }
-                    self.enabled = enabled;
-                    self.level = level;
+            Message::SetSchedule(edge, minute) => {
+                let Some(status) = self.status.as_mut() else {
+                    return Task::none();
+                };
+                match edge {
+                    Edge::From => status.schedule_from = minute,
+                    Edge::To => status.schedule_to = minute,
                 }
+                return self.call(move |proxy| async move {
+                    match edge {
+                        Edge::From => proxy.set_schedule_from(minute).await,
+                        Edge::To => proxy.set_schedule_to(minute).await,
+                    }
+                });
+            }
+            Message::Connected(proxy) => {
+                self.proxy = Some(proxy);
+            }
+            Message::UpdateState(status) => {
+                // a drag in progress still wins over the temperature, see `popup_content`
+                self.status = Some(status);
+            }
+            Message::Disconnected => {
+                self.status = None;
+                self.dragged_temperature = None;
+                self.preview_sent = None;
             }
             Message::Surface(a) => {
                 return cosmic::task::message(cosmic::Action::Cosmic(
                     cosmic::app::Action::Surface(a),
                 ));
             }
-            Message::NoOp => {}
+            Message::Tick | Message::NoOp => {}
         };
         Task::none()
     }
 
     fn view(&self) -> Element<'_, Message> {
         let have_popup = self.popup.clone();
-        let icon_name = if self.enabled { "weather-clear-night-symbolic" } else { "weather-clear-night-disabled-symbolic" };
+        let enabled = self.status.as_ref().is_some_and(|status| status.enabled);
+        let icon_name = if enabled { "weather-clear-night-symbolic" } else { "weather-clear-night-disabled-symbolic" };
         
         let btn = self
             .core
@@ -245,36 +497,7 @@ impl cosmic::Application for Window {
                             popup_settings
                         },
                         Some(Box::new(move |state: &Window| {
-                            let content_list = list_column()
-                                .padding(10)
-                                .spacing(10)
-                                .add(settings::item(
-                                    "Night Light",
-                                    cosmic::widget::container(
-                                        toggler(state.enabled)
-                                            .on_toggle(|value| Message::ToggleEnabled(value)),
-                                    )
-                                    .height(Length::Fixed(50.)),
-                                ))
-                                .add(settings::item(
-                                    "Auto (7PM - 7AM)",
-                                    cosmic::widget::container(
-                                        toggler(state.auto)
-                                            .on_toggle(|value| Message::ToggleAuto(value)),
-                                    )
-                                    .height(Length::Fixed(50.)),
-                                ))
-                                .add(settings::item(
-                                    "Intensity",
-                                    row![
-                                        button::text("Soft").on_press(Message::SetLevel(1)).class(if state.level == 1 { button::ButtonClass::Suggested } else { button::ButtonClass::Standard }),
-                                        button::text("Warm").on_press(Message::SetLevel(2)).class(if state.level == 2 { button::ButtonClass::Suggested } else { button::ButtonClass::Standard }),
-                                        button::text("Strong").on_press(Message::SetLevel(3)).class(if state.level == 3 { button::ButtonClass::Suggested } else { button::ButtonClass::Standard }),
-                                    ]
-                                    .spacing(5)
-                                    .align_y(Alignment::Center)
-                                ));
-                            Element::from(state.core.applet.popup_container(content_list))
+                            Element::from(state.core.applet.popup_container(state.popup_content()))
                                 .map(cosmic::Action::App)
                         })),
                     ))
@@ -299,6 +522,120 @@ impl cosmic::Application for Window {
     }
 }
 
+impl Window {
+    fn popup_content(&self) -> Element<'_, Message> {
+        let Some(status) = &self.status else {
+            return list_column()
+                .padding(10)
+                .add(text::body("Night light is unavailable, is cosmic-comp running?"))
+                .into();
+        };
+        let temperature = self.dragged_temperature.unwrap_or(status.temperature);
+        let mut content_list = list_column()
+            .padding(10)
+            .spacing(10)
+            .add(settings::item(
+                "Night Light",
+                cosmic::widget::container(
+                    toggler(status.enabled).on_toggle(|value| Message::ToggleEnabled(value)),
+                )
+                .height(Length::Fixed(50.)),
+            ))
+            .add(text::caption(status_line(status)))
+            .add(settings::item(
+                "Intensity",
+                row![
+                    choice("Soft", status.level == 1, Message::SetLevel(1)),
+                    choice("Warm", status.level == 2, Message::SetLevel(2)),
+                    choice("Strong", status.level == 3, Message::SetLevel(3)),
+                ]
+                .spacing(5)
+                .align_y(Alignment::Center),
+            ))
+            .add(settings::item(
+                format!("Temperature ({temperature}K)"),
+                slider(
+                    MIN_TEMPERATURE..=NEUTRAL_TEMPERATURE,
+                    temperature,
+                    Message::DragTemperature,
+                )
+                .on_release(Message::SetTemperature)
+                .step(100u32)
+                .width(Length::Fixed(160.)),
+            ))
+            .add(settings::item(
+                "Schedule",
+                row![
+                    choice(
+                        "Off",
+                        status.schedule_mode == ScheduleMode::Disabled,
+                        Message::SetScheduleMode(ScheduleMode::Disabled),
+                    ),
+                    choice(
+                        "Custom",
+                        status.schedule_mode == ScheduleMode::Custom,
+                        Message::SetScheduleMode(ScheduleMode::Custom),
+                    ),
+                    choice(
+                        "Sunset",
+                        status.schedule_mode == ScheduleMode::Solar,
+                        Message::SetScheduleMode(ScheduleMode::Solar),
+                    ),
+                ]
+                .spacing(5)
+                .align_y(Alignment::Center),
+            ));
+
+        match status.schedule_mode {
+            ScheduleMode::Custom => {
+                content_list = content_list
+                    .add(settings::item("From", time_picker(Edge::From, status.schedule_from)))
+                    .add(settings::item("To", time_picker(Edge::To, status.schedule_to)));
+            }
+            ScheduleMode::Solar if !status.has_location => {
+                content_list = content_list.add(text::caption(
+                    "Set your location with: night-light-ctl schedule set --location LAT,LON",
+                ));
+            }
+            _ => {}
+        }
+
+        content_list.into()
+    }
+}
+
+/// A button of a group where one option is picked
+fn choice(label: &'static str, selected: bool, message: Message) -> Element<'static, Message> {
+    button::text(label)
+        .on_press(message)
+        .class(if selected {
+            button::ButtonClass::Suggested
+        } else {
+            button::ButtonClass::Standard
+        })
+        .into()
+}
+
+/// Hour and minute dropdowns, each choice is a single write to the compositor
+fn time_picker(edge: Edge, minute: u16) -> Element<'static, Message> {
+    let (hour, minute) = (minute / 60, minute % 60);
+    row![
+        dropdown(&HOURS, Some(hour as usize), move |hour| {
+            Message::SetSchedule(edge, hour as u16 * 60 + minute)
+        }),
+        text::body(":"),
+        // times set elsewhere, e.g. with night-light-ctl, may fall between the entries
+        dropdown(
+            &MINUTES,
+            (minute % SCHEDULE_STEP == 0).then_some((minute / SCHEDULE_STEP) as usize),
+            move |index| Message::SetSchedule(edge, hour * 60 + index as u16 * SCHEDULE_STEP),
+        ),
+    ]
+    .spacing(5)
+    .align_y(Alignment::Center)
+    .into()
+}
+
 fn main() -> cosmic::iced::Result {
     let env = env_logger::Env::default()
         .filter_or("MY_LOG_LEVEL", "warn")
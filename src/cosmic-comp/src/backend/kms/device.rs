@@ -178,7 +178,7 @@ impl State {
         dev: dev_t,
         path: &Path,
         dh: &DisplayHandle,
-        night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+        night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
     ) -> Result<Vec<Output>> {
         if !self.backend.kms().session.is_active() {
             return Ok(Vec::new());
@@ -409,7 +409,7 @@ impl State {
     pub fn device_changed(
         &mut self,
         dev: dev_t,
-        night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+        night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
     ) -> Result<Vec<Output>> {
         if !self.backend.kms().session.is_active() {
             return Ok(Vec::new());
@@ -774,7 +774,7 @@ impl InnerDevice {
         evlh: &LoopHandle<'static, State>,
         screen_filter: ScreenFilter,
         shell: Arc<parking_lot::RwLock<Shell>>,
-        night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+        night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
         startup_done: Arc<AtomicBool>,
     ) -> Result<(Output, bool)> {
         let output = self
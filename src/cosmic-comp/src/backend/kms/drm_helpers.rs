@@ -15,6 +15,8 @@ use smithay::{
 };
 use std::{collections::HashMap, ops::Range};
 
+use crate::night_light::Adaptation;
+
 pub fn display_configuration(
     device: &mut impl ControlDevice,
     supports_atomic: bool,
@@ -316,3 +318,65 @@ pub fn panel_orientation(dev: &impl ControlDevice, conn: connector::Handle) -> R
         _ => Err(anyhow!("panel orientation has wrong value type")),
     }
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GammaRamp {
+    pub red: Vec<u16>,
+    pub green: Vec<u16>,
+    pub blue: Vec<u16>,
+}
+
+impl GammaRamp {
+    /// Splits a ramp laid out as all red, then all green, then all blue values.
+    pub fn from_planar(values: &[u16]) -> GammaRamp {
+        let size = values.len() / 3;
+        GammaRamp {
+            red: values[..size].to_vec(),
+            green: values[size..2 * size].to_vec(),
+            blue: values[2 * size..3 * size].to_vec(),
+        }
+    }
+
+    /// Runs every entry of the ramp through the night light, in linear light.
+    pub fn adapted(&self, night_light: &Adaptation) -> GammaRamp {
+        let adapt = |channel: &[u16], index: usize| {
+            channel
+                .iter()
+                .map(|val| {
+                    let value = *val as f32 / u16::MAX as f32;
+                    (night_light.apply_channel(index, value) * u16::MAX as f32).round() as u16
+                })
+                .collect::<Vec<_>>()
+        };
+        GammaRamp {
+            red: adapt(&self.red, 0),
+            green: adapt(&self.green, 1),
+            blue: adapt(&self.blue, 2),
+        }
+    }
+}
+
+pub fn gamma_size(dev: &impl ControlDevice, crtc: crtc::Handle) -> Result<u32> {
+    match dev.get_crtc(crtc)?.gamma_length() {
+        0 => Err(anyhow!("crtc has no gamma ramp")),
+        size => Ok(size),
+    }
+}
+
+pub fn get_gamma(dev: &impl ControlDevice, crtc: crtc::Handle) -> Result<GammaRamp> {
+    let size = gamma_size(dev, crtc)? as usize;
+
+    let mut ramp = GammaRamp {
+        red: vec![0; size],
+        green: vec![0; size],
+        blue: vec![0; size],
+    };
+    dev.get_gamma(crtc, &mut ramp.red, &mut ramp.green, &mut ramp.blue)?;
+    Ok(ramp)
+}
+
+/// Sets the legacy gamma ramp, which atomic drivers translate into the `GAMMA_LUT` property.
+pub fn set_gamma(dev: &impl ControlDevice, crtc: crtc::Handle, ramp: &GammaRamp) -> Result<()> {
+    dev.set_gamma(crtc, &ramp.red, &ramp.green, &ramp.blue)
+        .map_err(Into::into)
+}
@@ -55,6 +55,7 @@ pub mod render;
 mod socket;
 mod surface;
 use device::*;
+pub(crate) use drm_helpers::GammaRamp;
 pub(crate) use surface::Surface;
 pub use surface::Timings;
 
@@ -587,6 +588,14 @@ impl KmsState {
         Ok(())
     }
 
+    pub fn update_night_light(&mut self) {
+        for device in self.drm_devices.values_mut() {
+            for surface in device.inner.surfaces.values_mut() {
+                surface.update_night_light();
+            }
+        }
+    }
+
     pub fn refresh_used_devices(&mut self) -> Result<()> {
         let primary_node = self.primary_node.read().unwrap();
         let mut used_devices = HashSet::new();
@@ -711,7 +720,7 @@ impl KmsGuard<'_> {
         loop_handle: &LoopHandle<'static, State>,
         screen_filter: &ScreenFilter,
         shell: Arc<parking_lot::RwLock<Shell>>,
-        night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+        night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
         startup_done: Arc<AtomicBool>,
         clock: &Clock<Monotonic>,
     ) -> Result<(), anyhow::Error> {
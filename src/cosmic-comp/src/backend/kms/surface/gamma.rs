@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use smithay::reexports::drm::control::{Device as ControlDevice, crtc};
+use tracing::{debug, warn};
+
+use crate::{
+    backend::kms::drm_helpers::{self, GammaRamp},
+    night_light::Adaptation,
+};
+
+/// Shortest time between two ramps of a night light fade, every write is a blocking ioctl.
+const MIN_WRITE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Night light applied through the CRTC gamma ramp, which keeps direct scanout working.
+///
+/// Also programs ramps of gamma-control clients, which take precedence over the night light.
+#[derive(Debug, Default)]
+pub enum NightLightGamma {
+    /// We didn't need the gamma ramp yet
+    #[default]
+    Unknown,
+    /// Night light is applied through the gamma ramp
+    Active {
+        /// Ramp the CRTC had before we touched it
+        original: GammaRamp,
+        /// What is currently programmed, `None` if we need to re-apply
+        applied: Option<Applied>,
+        /// When we last programmed the night light
+        written: Option<Instant>,
+        /// A fade step was skipped to keep the writes down, another frame has to apply it
+        deferred: bool,
+    },
+    /// The driver doesn't support (or rejected) gamma ramps, the shader has to tint instead
+    Unsupported,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Applied {
+    NightLight(Adaptation),
+    Client,
+}
+
+impl NightLightGamma {
+    fn active(original: GammaRamp) -> Self {
+        NightLightGamma::Active {
+            original,
+            applied: None,
+            written: None,
+            deferred: false,
+        }
+    }
+
+    /// Programs the night light into the gamma ramp.
+    ///
+    /// Returns `false` if the shader needs to apply the night light instead.
+    pub fn apply(
+        &mut self,
+        dev: &impl ControlDevice,
+        crtc: crtc::Handle,
+        night_light: Adaptation,
+    ) -> bool {
+        if night_light.preserve_luminance && !night_light.is_neutral() {
+            // preserving luminance mixes the channels, which a per channel ramp can't do
+            self.restore(dev, crtc);
+            return false;
+        }
+
+        if let NightLightGamma::Unknown = self {
+            if night_light.is_neutral() {
+                // nothing to do, don't touch the CRTC
+                return true;
+            }
+            match drm_helpers::get_gamma(dev, crtc) {
+                Ok(original) => *self = NightLightGamma::active(original),
+                Err(err) if is_permanent(&err) => {
+                    debug!(
+                        ?err,
+                        ?crtc,
+                        "Gamma ramp unavailable, using shader for night light"
+                    );
+                    *self = NightLightGamma::Unsupported;
+                    return false;
+                }
+                Err(err) => {
+                    // try again on the next frame
+                    debug!(?err, ?crtc, "Gamma ramp busy, using shader for night light");
+                    return false;
+                }
+            }
+        }
+
+        let NightLightGamma::Active {
+            original,
+            applied,
+            written,
+            deferred,
+        } = self
+        else {
+            return false;
+        };
+        if *applied == Some(Applied::NightLight(night_light)) {
+            *deferred = false;
+            return true;
+        }
+        // during a fade the ramp may lag a few frames behind, the last step is never skipped
+        if matches!(applied, Some(Applied::NightLight(_)))
+            && written.is_some_and(|written| written.elapsed() < MIN_WRITE_INTERVAL)
+        {
+            *deferred = true;
+            return true;
+        }
+
+        match drm_helpers::set_gamma(dev, crtc, &original.adapted(&night_light)) {
+            Ok(()) => {
+                *applied = Some(Applied::NightLight(night_light));
+                *written = Some(Instant::now());
+                *deferred = false;
+                true
+            }
+            Err(err) => {
+                let _ = drm_helpers::set_gamma(dev, crtc, original);
+                *applied = None;
+                *deferred = false;
+                if is_permanent(&err) {
+                    warn!(
+                        ?err,
+                        ?crtc,
+                        "Failed to set gamma ramp, using shader for night light"
+                    );
+                    *self = NightLightGamma::Unsupported;
+                } else {
+                    // e.g. in the middle of a modeset, try again on the next frame
+                    debug!(?err, ?crtc, "Gamma ramp busy, using shader for night light");
+                }
+                false
+            }
+        }
+    }
+
+    /// Whether a skipped fade step still has to be written, which needs another frame.
+    pub fn is_deferred(&self) -> bool {
+        matches!(self, NightLightGamma::Active { deferred: true, .. })
+    }
+
+    /// Programs the ramp of a gamma-control client, unless it is already applied.
+    ///
+    /// Unlike `apply` this doesn't give up on the gamma ramp, if the driver rejects it,
+    /// since the client might just have sent us garbage.
+    pub fn apply_client(
+        &mut self,
+        dev: &impl ControlDevice,
+        crtc: crtc::Handle,
+        ramp: &GammaRamp,
+    ) -> anyhow::Result<()> {
+        if let NightLightGamma::Unknown = self {
+            *self = NightLightGamma::active(drm_helpers::get_gamma(dev, crtc)?);
+        }
+
+        let NightLightGamma::Active {
+            original, applied, ..
+        } = self
+        else {
+            anyhow::bail!("crtc has no usable gamma ramp");
+        };
+        if *applied == Some(Applied::Client) {
+            return Ok(());
+        }
+
+        if let Err(err) = drm_helpers::set_gamma(dev, crtc, ramp) {
+            let _ = drm_helpers::set_gamma(dev, crtc, original);
+            *applied = None;
+            return Err(err);
+        }
+        *applied = Some(Applied::Client);
+        Ok(())
+    }
+
+    /// Makes the next `apply` or `apply_client` program the CRTC, even if the ramp looks unchanged.
+    pub fn forget_applied(&mut self) {
+        if let NightLightGamma::Active { applied, .. } = self {
+            *applied = None;
+        }
+    }
+
+    /// Puts back the original ramp, e.g. before handing the device to another DRM master.
+    ///
+    /// The original is kept, so the next `apply` doesn't mistake our tint for it.
+    pub fn restore(&mut self, dev: &impl ControlDevice, crtc: crtc::Handle) {
+        if let NightLightGamma::Active {
+            original, applied, ..
+        } = self
+        {
+            let untouched = match applied.take() {
+                None => true,
+                Some(Applied::NightLight(night_light)) => night_light.is_neutral(),
+                Some(Applied::Client) => false,
+            };
+            if !untouched {
+                if let Err(err) = drm_helpers::set_gamma(dev, crtc, original) {
+                    debug!(?err, ?crtc, "Failed to restore gamma ramp");
+                }
+            }
+        }
+    }
+}
+
+/// Whether `err` means the CRTC never takes a gamma ramp, rather than just not right now,
+/// e.g. `EBUSY` during a modeset or `EACCES` while another session is DRM master.
+fn is_permanent(err: &anyhow::Error) -> bool {
+    match err
+        .downcast_ref::<io::Error>()
+        .and_then(io::Error::raw_os_error)
+    {
+        Some(errno) => matches!(
+            errno,
+            libc::ENOSYS | libc::EINVAL | libc::EOPNOTSUPP | libc::ENOTTY
+        ),
+        // not an ioctl failure, e.g. a CRTC without a gamma ramp
+        None => true,
+    }
+}
@@ -3,11 +3,17 @@
 use crate::{
     backend::render::{
         CLEAR_COLOR, CursorMode, GlMultiError, GlMultiRenderer, PostprocessOutputConfig,
-        PostprocessShader, PostprocessState, ScreenFilterStorage, render_output,
+        PostprocessState, ScreenFilterStorage, render_output,
         element::{CosmicElement, DamageElement},
         init_shaders, output_elements,
+        postprocess::PostprocessElement,
+        tint_framebuffer,
     },
     config::ScreenFilter,
+    night_light::{
+        Adaptation, OutputKey,
+        capture::{CaptureSource, CaptureTint},
+    },
     shell::Shell,
     state::SurfaceDmabufFeedback,
     utils::prelude::*,
@@ -50,9 +56,7 @@ use smithay::{
                     constrain_render_elements,
                 },
             },
-            gles::{
-                GlesRenderbuffer, GlesRenderer, GlesTexture, Uniform, element::TextureShaderElement,
-            },
+            gles::{GlesRenderbuffer, GlesTexture},
             glow::GlowRenderer,
             multigpu::{ApiDevice, Error as MultiError, GpuManager},
             sync::SyncPoint,
@@ -88,7 +92,7 @@ use smithay::{
 use tracing::{error, info, trace, warn};
 
 use std::{
-    borrow::{Borrow, BorrowMut},
+    borrow::BorrowMut,
     collections::{HashMap, HashSet, hash_map},
     mem,
     sync::{
@@ -100,10 +104,15 @@ use std::{
     time::Duration,
 };
 
+mod gamma;
 mod timings;
+use self::gamma::NightLightGamma;
 pub use self::timings::Timings;
 
-use super::{drm_helpers, render::gles::GbmGlowBackend};
+use super::{
+    drm_helpers::{self, GammaRamp},
+    render::gles::GbmGlowBackend,
+};
 
 #[cfg(feature = "debug")]
 use smithay_egui::EguiState;
@@ -149,7 +158,10 @@ pub struct SurfaceThreadState {
     postprocess_textures: HashMap<DrmNode, PostprocessState>,
 
     shell: Arc<parking_lot::RwLock<Shell>>,
-    pub night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+    pub night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
+    night_light_gamma: NightLightGamma,
+    /// Ramp of a gamma-control client, suspends the night light while set
+    client_gamma: Option<GammaRamp>,
 
     loop_handle: LoopHandle<'static, Self>,
     clock: Clock<Monotonic>,
@@ -215,6 +227,9 @@ pub enum ThreadCommand {
     },
     UpdateMirroring(Option<Output>),
     UpdateScreenFilter(ScreenFilter),
+    UpdateNightLight,
+    GammaSize(SyncSender<Result<u32>>),
+    SetGamma(Option<GammaRamp>, SyncSender<Result<()>>),
     VBlank(Option<DrmEventMetadata>),
     ScheduleRender,
     AdaptiveSyncAvailable(SyncSender<Result<VrrSupport>>),
@@ -249,7 +264,7 @@ impl Surface {
         evlh: &LoopHandle<'static, State>,
         screen_filter: ScreenFilter,
         shell: Arc<parking_lot::RwLock<Shell>>,
-        night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+        night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
         startup_done: Arc<AtomicBool>,
     ) -> Result<Self> {
         let (tx, rx) = channel::<ThreadCommand>();
@@ -408,6 +423,23 @@ impl Surface {
             .send(ThreadCommand::UpdateScreenFilter(config));
     }
 
+    pub fn update_night_light(&mut self) {
+        let _ = self.thread_command.send(ThreadCommand::UpdateNightLight);
+    }
+
+    pub fn gamma_size(&self) -> Result<u32> {
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let _ = self.thread_command.send(ThreadCommand::GammaSize(tx));
+        rx.recv().context("Surface thread died")?
+    }
+
+    /// Overrides the gamma ramp (and with it the night light), `None` hands it back to the night light.
+    pub fn set_gamma(&mut self, ramp: Option<GammaRamp>) -> Result<()> {
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let _ = self.thread_command.send(ThreadCommand::SetGamma(ramp, tx));
+        rx.recv().context("Surface thread died")?
+    }
+
     pub fn adaptive_sync_support(&self) -> Result<VrrSupport> {
         let (tx, rx) = std::sync::mpsc::sync_channel(1);
         let _ = self
@@ -499,7 +531,7 @@ fn surface_thread(
     shell: Arc<parking_lot::RwLock<Shell>>,
     active: Arc<AtomicBool>,
     screen_filter: ScreenFilter,
-    night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+    night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
     thread_sender: Sender<SurfaceCommand>,
     thread_receiver: Channel<ThreadCommand>,
     startup_done: Arc<AtomicBool>,
@@ -548,6 +580,8 @@ fn surface_thread(
         mirroring: None,
         screen_filter,
         night_light,
+        night_light_gamma: NightLightGamma::default(),
+        client_gamma: None,
         postprocess_textures: HashMap::new(),
         shell,
         loop_handle: event_loop.handle(),
@@ -603,6 +637,27 @@ fn surface_thread(
             Event::Msg(ThreadCommand::UpdateScreenFilter(filter_config)) => {
                 state.update_screen_filter(filter_config);
             }
+            Event::Msg(ThreadCommand::UpdateNightLight) => {
+                state.update_night_light();
+                if startup_done.load(Ordering::SeqCst) {
+                    state.queue_redraw(false);
+                }
+            }
+            Event::Msg(ThreadCommand::GammaSize(result)) => {
+                if let Some(compositor) = state.compositor.as_mut() {
+                    let _ = result.send(compositor.with_compositor(|c| {
+                        drm_helpers::gamma_size(c.surface(), c.surface().crtc())
+                    }));
+                } else {
+                    let _ = result.send(Err(anyhow::anyhow!("Gamma size of inactive surface")));
+                }
+            }
+            Event::Msg(ThreadCommand::SetGamma(ramp, result)) => {
+                let _ = result.send(state.set_client_gamma(ramp));
+                if startup_done.load(Ordering::SeqCst) {
+                    state.queue_redraw(false);
+                }
+            }
             Event::Msg(ThreadCommand::AdaptiveSyncAvailable(result)) => {
                 if let Some(compositor) = state.compositor.as_mut() {
                     let _ = result.send(
@@ -658,6 +713,7 @@ fn surface_thread(
                 }
             }
             Event::Closed | Event::Msg(ThreadCommand::End) => {
+                state.restore_gamma();
                 signal.stop();
                 signal.wakeup();
             }
@@ -671,6 +727,7 @@ fn surface_thread(
 impl SurfaceThreadState {
     fn suspend(&mut self, tx: SyncSender<()>) {
         self.active.store(false, Ordering::SeqCst);
+        self.restore_gamma();
         let _ = self.compositor.take();
 
         match std::mem::replace(&mut self.state, QueueState::Idle) {
@@ -884,7 +941,11 @@ impl SurfaceThreadState {
             QueueState::WaitingForEstimatedVBlankAndQueued { .. } => unreachable!(),
         };
 
-        if redraw_needed || self.shell.read().animations_going() {
+        if redraw_needed
+            || self.shell.read().animations_going()
+            || self.night_light.lock().is_animating()
+            || self.night_light_gamma.is_deferred()
+        {
             let vblank_frame = tracy_client::Client::running()
                 .unwrap()
                 .non_continuous_frame(self.vblank_frame_name);
@@ -911,7 +972,11 @@ impl SurfaceThreadState {
 
         self.frame_callback_seq = self.frame_callback_seq.wrapping_add(1);
 
-        if force || self.shell.read().animations_going() {
+        if force
+            || self.shell.read().animations_going()
+            || self.night_light.lock().is_animating()
+            || self.night_light_gamma.is_deferred()
+        {
             self.queue_redraw(false);
         }
         self.send_frame_callbacks();
@@ -1082,22 +1147,71 @@ impl SurfaceThreadState {
 
         // we can't use the elements after `compositor.render_frame`,
         // so let's collect everything we need for screencopy now
-        let mut has_cursor_mode_none = false;
+        let night_light_exempt = if self.mirroring.is_none() {
+            self.shell.read().night_light_exempt_regions(&self.output)
+        } else {
+            Vec::new()
+        };
+        let capture_tint = self
+            .night_light
+            .lock()
+            .capture_adaptation(CaptureSource::Output, &OutputKey::from(&self.output))
+            .map(|adaptation| {
+                CaptureTint::new(
+                    adaptation,
+                    &night_light_exempt,
+                    self.output.current_scale().fractional_scale(),
+                )
+            });
         let frames = self
             .mirroring
             .is_none()
-            .then(|| take_screencopy_frames(&self.output, &elements, &mut has_cursor_mode_none))
+            .then(|| take_screencopy_frames(&self.output, &elements, capture_tint.as_ref()))
             .unwrap_or_default();
 
         // actual rendering
+        let (mut night_light, mut night_light_active) = {
+            let output = OutputKey::from(&self.output);
+            let night_light = self.night_light.lock();
+            (
+                night_light.adaptation(&output),
+                !night_light.is_noop(&output),
+            )
+        };
+        // prefer the gamma ramp, so we don't need an offscreen pass
+        if compositor.with_compositor(|c| match self.client_gamma.as_ref() {
+            // a gamma-control client took over, it is responsible for any tint now
+            Some(ramp) => {
+                if let Err(err) =
+                    self.night_light_gamma
+                        .apply_client(c.surface(), c.surface().crtc(), ramp)
+                {
+                    warn!(?err, "Failed to re-apply client gamma ramp");
+                }
+                true
+            }
+            // a ramp tints the whole output, exempt windows need the shader
+            None if !night_light_exempt.is_empty() => {
+                self.night_light_gamma
+                    .apply(c.surface(), c.surface().crtc(), Adaptation::NEUTRAL);
+                false
+            }
+            None => self
+                .night_light_gamma
+                .apply(c.surface(), c.surface().crtc(), night_light),
+        }) {
+            night_light = Adaptation::NEUTRAL;
+            night_light_active = false;
+        }
+        let filter_active = !self.screen_filter.is_noop() || night_light_active;
         let source_output = self
             .mirroring
             .as_ref()
-            .or((!self.screen_filter.is_noop()).then_some(&self.output))
+            .or(filter_active.then_some(&self.output))
             .filter(|output| {
                 PostprocessOutputConfig::for_output_untransformed(output)
                     != PostprocessOutputConfig::for_output(&self.output)
-                    || !self.screen_filter.is_noop()
+                    || filter_active
             });
 
         let mut pre_postprocess_data = PrePostprocessData::default();
@@ -1127,7 +1241,15 @@ impl SurfaceThreadState {
                 }
             };
 
-            if has_cursor_mode_none && self.mirroring.is_none() {
+            // the uniforms change the whole output, without touching the offscreen textures
+            let night_light_changed = postprocess_state.night_light != night_light;
+            postprocess_state.night_light = night_light;
+            let exempt_damage =
+                postprocess_state.update_night_light_exempt(source_output, &night_light_exempt);
+
+            // the offscreen pass doesn't render cursors, so they get the same filters
+            // through their own texture, no matter if they end up on a cursor plane
+            if self.mirroring.is_none() {
                 // TODO: use `extract_if` once stablized
                 let cursor_element_count = elements
                     .iter()
@@ -1203,7 +1325,7 @@ impl SurfaceThreadState {
                             let transform = source_output.current_transform();
                             let area = tex.size().to_logical(1, transform);
 
-                            Ok(res
+                            let mut damage = res
                                 .damage
                                 .cloned()
                                 .map(|v| {
@@ -1211,7 +1333,11 @@ impl SurfaceThreadState {
                                         .map(|r| r.to_logical(1).to_buffer(1, transform, &area))
                                         .collect::<Vec<_>>()
                                 })
-                                .unwrap_or_default())
+                                .unwrap_or_default();
+                            if night_light_changed {
+                                damage.push(Rectangle::from_size(tex.size()));
+                            }
+                            Ok(damage)
                         })
                         .context("Failed to draw to offscreen render target")?;
                 }
@@ -1229,7 +1355,6 @@ impl SurfaceThreadState {
 
                     let mut fb = renderer.bind(tex)?;
                     let mut screen_filter_state = ScreenFilterStorage::default();
-                    screen_filter_state.night_light = Some(self.night_light.clone());
 
                     let res = match render_output(
                         Some(&self.target_node),
@@ -1264,7 +1389,7 @@ impl SurfaceThreadState {
                     let transform = source_output.current_transform();
                     let area = tex.size().to_logical(1, transform);
 
-                    Ok(res
+                    let mut damage = res
                         .damage
                         .cloned()
                         .map(|v| {
@@ -1272,7 +1397,12 @@ impl SurfaceThreadState {
                                 .map(|r| r.to_logical(1).to_buffer(1, transform, &area))
                                 .collect::<Vec<_>>()
                         })
-                        .unwrap_or_default())
+                        .unwrap_or_default();
+                    if night_light_changed {
+                        damage.push(Rectangle::from_size(tex.size()));
+                    }
+                    damage.extend(exempt_damage.iter().copied());
+                    Ok(damage)
                 })
                 .context("Failed to draw to offscreen render target")?;
 
@@ -1367,6 +1497,7 @@ impl SurfaceThreadState {
                                 &frame_result,
                                 &elements,
                                 (&session, frame, res),
+                                capture_tint.as_ref(),
                                 now.into(),
                             ) {
                                 tracing::warn!(?err, "Failed to screencopy");
@@ -1464,6 +1595,43 @@ impl SurfaceThreadState {
         self.postprocess_textures.clear();
     }
 
+    fn update_night_light(&mut self) {
+        self.postprocess_textures.clear();
+    }
+
+    fn set_client_gamma(&mut self, ramp: Option<GammaRamp>) -> Result<()> {
+        self.client_gamma = None;
+        self.postprocess_textures.clear();
+
+        let Some(ramp) = ramp else {
+            // the client is gone, the next frame puts our own ramp back
+            self.restore_gamma();
+            return Ok(());
+        };
+        // clients send a new ramp on every step of their own fades, so write it right away
+        // instead of flashing the original ramp in between
+        self.night_light_gamma.forget_applied();
+        let Some(compositor) = self.compositor.as_mut() else {
+            anyhow::bail!("Set gamma on inactive surface");
+        };
+        compositor.with_compositor(|c| {
+            self.night_light_gamma
+                .apply_client(c.surface(), c.surface().crtc(), &ramp)
+        })?;
+        self.client_gamma = Some(ramp);
+        Ok(())
+    }
+
+    /// Hands the CRTC back with the gamma ramp we found on it.
+    fn restore_gamma(&mut self) {
+        if let Some(compositor) = self.compositor.as_mut() {
+            compositor.with_compositor(|c| {
+                self.night_light_gamma
+                    .restore(c.surface(), c.surface().crtc())
+            });
+        }
+    }
+
     fn send_frame_callbacks(&mut self) {
         if self.mirroring.is_none() {
             let _ = self
@@ -1611,7 +1779,7 @@ fn get_surface_dmabuf_feedback(
 fn take_screencopy_frames(
     output: &Output,
     elements: &[CosmicElement<GlMultiRenderer>],
-    has_cursor_mode_none: &mut bool,
+    tint: Option<&CaptureTint>,
 ) -> Vec<(
     ScreencopySessionRef,
     ScreencopyFrame,
@@ -1629,6 +1797,10 @@ fn take_screencopy_frames(
             let age = if matches!(buffer_type(&buffer), Some(BufferType::Shm)) {
                 // TODO re-use offscreen buffer to damage track screencopy to shm
                 0
+            } else if damage_tracking.tint.as_ref() != tint {
+                // the undamaged parts of the buffer still carry the old tint
+                damage_tracking.tint = tint.cloned();
+                0
             } else {
                 1
             };
@@ -1663,10 +1835,6 @@ fn take_screencopy_frames(
 
             let res = damage_tracking.dt.damage_output(age, elements);
 
-            if !session.draw_cursor() {
-                *has_cursor_mode_none = true;
-            }
-
             let res = res.map(|(a, b)| (a.cloned(), b));
             std::mem::drop(damage_tracking);
             (session, frame, res)
@@ -1686,6 +1854,7 @@ fn send_screencopy_result<'a>(
         ScreencopyFrame,
         Result<(Option<Vec<Rectangle<i32, Physical>>>, RenderElementStates), OutputNoMode>,
     ),
+    tint: Option<&CaptureTint>,
     presentation_time: Duration,
 ) -> Result<()> {
     let (damage, _) = res?;
@@ -1718,6 +1887,8 @@ fn send_screencopy_result<'a>(
             .as_ref()
             .is_some_and(|tex| tex.format() == Some(format))
             && (!session.draw_cursor() || pre_postprocess_data.cursor_texture.is_none())
+            // the tint must not end up in the texture we read from
+            && tint.is_none()
         {
             None
         } else {
@@ -1753,21 +1924,18 @@ fn send_screencopy_result<'a>(
             .flatten();
 
         // If the screen is rotated, we must convert damage to match output.
-        let adjusted = damage
-            .iter()
-            .copied()
-            .map(|rect| {
-                let logical = rect.to_logical(1);
-                logical
-                    .to_buffer(
-                        1,
-                        output_transform.invert(),
-                        &buffer_size.to_logical(1, output_transform),
-                    )
-                    .to_logical(1, Transform::Normal, &buffer_size)
-                    .to_physical(1)
-            })
-            .collect::<Vec<_>>();
+        let to_buffer = |rect: Rectangle<i32, Physical>| {
+            let logical = rect.to_logical(1);
+            logical
+                .to_buffer(
+                    1,
+                    output_transform.invert(),
+                    &buffer_size.to_logical(1, output_transform),
+                )
+                .to_logical(1, Transform::Normal, &buffer_size)
+                .to_physical(1)
+        };
+        let adjusted = damage.iter().copied().map(to_buffer).collect::<Vec<_>>();
 
         if let Some(tex) = pre_postprocess_data.texture.as_mut() {
             let tex_fb = renderer
@@ -1850,6 +2018,24 @@ fn send_screencopy_result<'a>(
                     }
                 })?;
         };
+
+        if let Some((tint, fb)) = tint.zip(fb.as_mut()).filter(|_| !adjusted.is_empty()) {
+            let exempt = tint
+                .exempt
+                .iter()
+                .copied()
+                .map(to_buffer)
+                .collect::<Vec<_>>();
+            sync = tint_framebuffer(
+                renderer,
+                fb,
+                buffer_size.to_logical(1, Transform::Normal).to_physical(1),
+                &adjusted,
+                &exempt,
+                tint.adaptation,
+            )
+            .map_err(RenderError::<<GlMultiRenderer as RendererSuper>::Error>::Rendering)?;
+        }
     }
 
     let transform = output.current_transform();
@@ -1880,13 +2066,7 @@ fn postprocess_elements<'a>(
     postprocess_state: &PostprocessState,
     screen_filter: &ScreenFilter,
 ) -> Vec<CosmicElement<GlMultiRenderer<'a>>> {
-    let postprocess_texture_shader = Borrow::<GlesRenderer>::borrow(renderer.as_ref())
-        .egl_context()
-        .user_data()
-        .get::<PostprocessShader>()
-        .expect("OffscreenShader should be available through `init_shaders`");
-
-    let mut elements: [Option<TextureShaderElement>; 2] = [None, None];
+    let mut elements: [Option<PostprocessElement>; 2] = [None, None];
     if let Some(cursor_texture) = postprocess_state.cursor_texture.as_ref() {
         let cursor_geometry = pre_postprocess_data.cursor_geometry.unwrap();
         let texture_elem = TextureRenderElement::from_texture_render_buffer(
@@ -1907,19 +2087,12 @@ fn postprocess_elements<'a>(
             Kind::Cursor,
         );
 
-        elements[0] = Some(TextureShaderElement::new(
+        elements[0] = Some(PostprocessElement::new(
+            &*renderer,
             texture_elem,
-            postprocess_texture_shader.0.clone(),
-            vec![
-                Uniform::new("invert", if screen_filter.inverted { 1. } else { 0. }),
-                Uniform::new(
-                    "color_mode",
-                    screen_filter
-                        .color_filter
-                        .map(|val| val as u8 as f32)
-                        .unwrap_or(0.),
-                ),
-            ],
+            screen_filter,
+            postprocess_state.night_light,
+            Vec::new(),
         ));
     }
 
@@ -1941,19 +2114,12 @@ fn postprocess_elements<'a>(
         ),
         Kind::Unspecified,
     );
-    elements[1] = Some(TextureShaderElement::new(
+    elements[1] = Some(PostprocessElement::new(
+        &*renderer,
         texture_elem,
-        postprocess_texture_shader.0.clone(),
-        vec![
-            Uniform::new("invert", if screen_filter.inverted { 1. } else { 0. }),
-            Uniform::new(
-                "color_mode",
-                screen_filter
-                    .color_filter
-                    .map(|val| val as u8 as f32)
-                    .unwrap_or(0.),
-            ),
-        ],
+        screen_filter,
+        postprocess_state.night_light,
+        postprocess_state.night_light_exempt.clone(),
     ));
 
     constrain_render_elements(
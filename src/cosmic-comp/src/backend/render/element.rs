@@ -11,14 +11,14 @@ use smithay::{
             surface::WaylandSurfaceRenderElement,
             utils::{CropRenderElement, Relocate, RelocateRenderElement, RescaleRenderElement},
         },
-        gles::{GlesError, element::TextureShaderElement},
+        gles::GlesError,
         glow::{GlowFrame, GlowRenderer},
         utils::{CommitCounter, DamageSet, OpaqueRegions},
     },
     utils::{Buffer as BufferCoords, Logical, Physical, Point, Rectangle, Scale},
 };
 
-use super::{GlMultiRenderer, cursor::CursorRenderElement};
+use super::{GlMultiRenderer, cursor::CursorRenderElement, postprocess::PostprocessElement};
 
 pub enum CosmicElement<R>
 where
@@ -33,7 +33,7 @@ where
     Dnd(WaylandSurfaceRenderElement<R>),
     MoveGrab(RescaleRenderElement<CosmicMappedRenderElement<R>>),
     Postprocess(
-        CropRenderElement<RelocateRenderElement<RescaleRenderElement<TextureShaderElement>>>,
+        CropRenderElement<RelocateRenderElement<RescaleRenderElement<PostprocessElement>>>,
     ),
     Zoom(MemoryRenderBufferRenderElement<R>),
     #[cfg(feature = "debug")]
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use std::{
-    borrow::Borrow,
+    borrow::{Borrow, BorrowMut},
     cell::RefCell,
     collections::HashMap,
     ops::ControlFlow,
@@ -21,6 +21,7 @@ use crate::{
         },
     },
     config::ScreenFilter,
+    night_light::Adaptation,
     shell::{
         CosmicMappedRenderElement, OverviewMode, SeatExt, Trigger, WorkspaceDelta,
         WorkspaceRenderElement,
@@ -49,8 +50,8 @@ use smithay::{
         allocator::{Fourcc, dmabuf::Dmabuf},
         drm::{DrmDeviceFd, DrmNode},
         renderer::{
-            Bind, Blit, Color32F, ExportMem, ImportAll, ImportMem, Offscreen, Renderer, Texture,
-            TextureFilter,
+            Bind, Blit, Color32F, ExportMem, Frame, ImportAll, ImportMem, Offscreen, Renderer,
+            Texture, TextureFilter,
             damage::{Error as RenderError, OutputDamageTracker, RenderOutputResult},
             element::{
                 Element, Id, Kind, RenderElement, WeakId,
@@ -62,9 +63,9 @@ use smithay::{
                 },
             },
             gles::{
-                GlesError, GlesPixelProgram, GlesRenderer, GlesTexProgram, GlesTexture, Uniform,
-                UniformName, UniformType,
-                element::{PixelShaderElement, TextureShaderElement},
+                GlesError, GlesFrame, GlesPixelProgram, GlesRenderer, GlesTexProgram, GlesTexture,
+                Uniform, UniformName, UniformType,
+                element::PixelShaderElement,
             },
             glow::GlowRenderer,
             multigpu::{Error as MultiError, MultiFrame, MultiRenderer},
@@ -74,7 +75,8 @@ use smithay::{
     input::Seat,
     output::{Output, OutputModeSource, OutputNoMode},
     utils::{
-        IsAlive, Logical, Monotonic, Physical, Point, Rectangle, Scale, Size, Time, Transform,
+        Buffer as BufferCoords, IsAlive, Logical, Monotonic, Physical, Point, Rectangle, Scale,
+        Size, Time, Transform,
     },
     wayland::{dmabuf::get_dmabuf, session_lock::LockSurface},
 };
@@ -86,8 +88,12 @@ pub mod animations;
 pub mod clipped_surface;
 pub mod cursor;
 pub mod element;
+pub mod postprocess;
 pub mod shadow;
-use self::element::{AsGlowRenderer, CosmicElement};
+use self::{
+    element::{AsGlowRenderer, CosmicElement},
+    postprocess::PostprocessElement,
+};
 
 use super::kms::Timings;
 
@@ -384,6 +390,18 @@ impl BackdropShader {
 
 pub struct PostprocessShader(pub GlesTexProgram);
 
+impl PostprocessShader {
+    pub fn get<R: AsGlowRenderer>(renderer: &R) -> GlesTexProgram {
+        Borrow::<GlesRenderer>::borrow(renderer.glow_renderer())
+            .egl_context()
+            .user_data()
+            .get::<PostprocessShader>()
+            .expect("OffscreenShader should be available through `init_shaders`")
+            .0
+            .clone()
+    }
+}
+
 pub fn init_shaders(renderer: &mut GlesRenderer) -> Result<(), GlesError> {
     {
         let egl_context = renderer.egl_context();
@@ -416,7 +434,10 @@ pub fn init_shaders(renderer: &mut GlesRenderer) -> Result<(), GlesError> {
         &[
             UniformName::new("invert", UniformType::_1f),
             UniformName::new("color_mode", UniformType::_1f),
-            UniformName::new("night_light_level", UniformType::_1f),
+            UniformName::new("color_matrix", UniformType::Matrix3x3),
+            UniformName::new("night_light_color", UniformType::_3f),
+            UniformName::new("night_light_brightness", UniformType::_1f),
+            UniformName::new("night_light_preserve_luminance", UniformType::_1f),
         ],
     )?;
     let clipping_shader = renderer.compile_custom_texture_shader(
@@ -461,6 +482,58 @@ pub fn init_shaders(renderer: &mut GlesRenderer) -> Result<(), GlesError> {
     Ok(())
 }
 
+/// Applies the night light to the `damage` of an already rendered framebuffer.
+///
+/// Captures are rendered without the postprocess pass, this adds the tint afterwards.
+/// Only the damage is touched, so reused buffers don't get tinted twice,
+/// and `exempt` regions are left out, like `PostprocessElement` does on screen.
+pub fn tint_framebuffer<R>(
+    renderer: &mut R,
+    fb: &mut R::Framebuffer<'_>,
+    size: Size<i32, Physical>,
+    damage: &[Rectangle<i32, Physical>],
+    exempt: &[Rectangle<i32, Physical>],
+    night_light: Adaptation,
+) -> Result<SyncPoint, R::Error>
+where
+    R: Offscreen<GlesTexture> + Blit + AsGlowRenderer,
+    R::Error: FromGlesError,
+{
+    let damage = &Rectangle::subtract_rects_many(damage.iter().copied(), exempt.iter().copied());
+    let buffer_size = size.to_logical(1).to_buffer(1, Transform::Normal);
+    // the shader can't sample the framebuffer it renders to, so work on a copy
+    let mut texture =
+        Offscreen::<GlesTexture>::create_buffer(renderer, Fourcc::Abgr8888, buffer_size)?;
+    {
+        let mut texture_fb = renderer.bind(&mut texture)?;
+        for rect in damage {
+            let sync = renderer.blit(fb, &mut texture_fb, *rect, *rect, TextureFilter::Nearest)?;
+            renderer.wait(&sync)?;
+        }
+    }
+
+    let shader = PostprocessShader::get(renderer);
+    let mut frame = renderer.render(fb, size, Transform::Normal)?;
+    let glow_frame = R::glow_frame_mut(&mut frame);
+    BorrowMut::<GlesFrame>::borrow_mut(&mut *glow_frame).override_default_tex_program(
+        shader,
+        postprocess::uniforms(&ScreenFilter::default(), night_light),
+    );
+    // damage doubles as opaque region, to replace the pixels instead of blending over them
+    let res = glow_frame.render_texture_from_to(
+        &texture,
+        Rectangle::from_size(buffer_size).to_f64(),
+        Rectangle::from_size(size),
+        damage,
+        damage,
+        Transform::Normal,
+        1.0,
+    );
+    BorrowMut::<GlesFrame>::borrow_mut(glow_frame).clear_tex_program_override();
+    res.map_err(R::Error::from_gles_error)?;
+    frame.finish()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CursorMode {
     None,
@@ -1059,6 +1132,10 @@ pub struct PostprocessState {
     pub cursor_texture: Option<TextureRenderBuffer<GlesTexture>>,
     pub cursor_damage_tracker: Option<OutputDamageTracker>,
     pub output_config: PostprocessOutputConfig,
+    /// Night light used for the last frame, to damage everything when it changes
+    pub night_light: Adaptation,
+    /// Regions shown without night light in the last frame, in buffer coordinates of `texture`
+    pub night_light_exempt: Vec<Rectangle<i32, BufferCoords>>,
 }
 
 impl PostprocessState {
@@ -1090,6 +1167,8 @@ impl PostprocessState {
             cursor_texture: None,
             cursor_damage_tracker: None,
             output_config,
+            night_light: Adaptation::NEUTRAL,
+            night_light_exempt: Vec::new(),
         })
     }
 
@@ -1140,6 +1219,41 @@ impl PostprocessState {
         self.cursor_texture.take();
         self.cursor_damage_tracker.take();
     }
+
+    /// Stores the `regions` of `output` to be shown without night light.
+    ///
+    /// Returns the damage to the texture, covering regions that started or stopped being exempt.
+    pub fn update_night_light_exempt(
+        &mut self,
+        output: &Output,
+        regions: &[Rectangle<i32, Logical>],
+    ) -> Vec<Rectangle<i32, BufferCoords>> {
+        let scale = self.output_config.fractional_scale;
+        let transform = output.current_transform();
+        let area = self
+            .output_config
+            .size
+            .to_logical(1)
+            .to_buffer(1, Transform::Normal)
+            .to_logical(1, transform);
+        let exempt = regions
+            .iter()
+            .map(|rect| {
+                rect.to_physical_precise_up(scale)
+                    .to_logical(1)
+                    .to_buffer(1, transform, &area)
+            })
+            .collect::<Vec<_>>();
+
+        if exempt == self.night_light_exempt {
+            return Vec::new();
+        }
+        let previous = std::mem::replace(&mut self.night_light_exempt, exempt);
+        previous
+            .into_iter()
+            .chain(self.night_light_exempt.iter().copied())
+            .collect()
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -1178,7 +1292,19 @@ impl PostprocessOutputConfig {
 pub struct ScreenFilterStorage {
     pub filter: ScreenFilter,
     pub state: Option<PostprocessState>,
-    pub night_light: Option<std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>>,
+    pub night_light: Option<std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>>,
+}
+
+impl ScreenFilterStorage {
+    /// Whether neither the accessibility filter nor night light need a postprocess pass on `output`
+    pub fn is_noop(&self, output: &Output) -> bool {
+        self.filter.is_noop()
+            && self.night_light.as_ref().is_none_or(|night_light| {
+                night_light
+                    .lock()
+                    .is_noop(&crate::night_light::OutputKey::from(output))
+            })
+    }
 }
 
 #[profiling::function]
@@ -1222,8 +1348,21 @@ where
         .map(|((w, start), idx)| (w.handle, idx, start));
     let workspace = (workspace.handle, idx);
     let zoom_state = shell_ref.zoom_state().cloned();
+    let night_light_exempt = shell_ref.night_light_exempt_regions(output);
     std::mem::drop(shell_ref);
 
+    let capture_tint = screen_filter.night_light.as_ref().and_then(|night_light| {
+        let adaptation = night_light.lock().capture_adaptation(
+            crate::night_light::capture::CaptureSource::Output,
+            &crate::night_light::OutputKey::from(output),
+        )?;
+        Some(crate::night_light::capture::CaptureTint::new(
+            adaptation,
+            &night_light_exempt,
+            output.current_scale().fractional_scale(),
+        ))
+    });
+
     let element_filter = if workspace_overview_is_open(output) {
         ElementFilter::LayerShellOnly
     } else {
@@ -1231,7 +1370,7 @@ where
     };
 
     let mut postprocess_texture = None;
-    let result = if !screen_filter.filter.is_noop() {
+    let result = if !screen_filter.is_noop(output) {
         if screen_filter.state.as_ref().is_none_or(|state| {
             state.output_config != PostprocessOutputConfig::for_output_untransformed(output)
         }) {
@@ -1247,8 +1386,21 @@ where
 
         let night_light_clone = screen_filter.night_light.clone();
         let filter_clone = screen_filter.filter.clone();
-        
+        let night_light = screen_filter
+            .night_light
+            .as_ref()
+            .map(|night_light| {
+                night_light
+                    .lock()
+                    .adaptation(&crate::night_light::OutputKey::from(output))
+            })
+            .unwrap_or_default();
+
         let state = screen_filter.state.as_mut().unwrap();
+        // the uniforms change the whole output, without touching the offscreen texture
+        let night_light_changed = state.night_light != night_light;
+        state.night_light = night_light;
+        let exempt_damage = state.update_night_light_exempt(output, &night_light_exempt);
         let mut result = Err(RenderError::OutputNoMode(OutputNoMode));
         state
             .texture
@@ -1281,7 +1433,7 @@ where
                 std::mem::drop(target);
                 postprocess_texture = Some(tex.clone());
 
-                Ok(if let Ok((res, _)) = result.as_ref() {
+                let mut damage = if let Ok((res, _)) = result.as_ref() {
                     renderer.wait(&res.sync).map_err(RenderError::Rendering)?;
                     let transform = output.current_transform();
                     let area = tex.size().to_logical(1, transform);
@@ -1296,7 +1448,13 @@ where
                         .unwrap_or_default()
                 } else {
                     Vec::new()
-                })
+                };
+                if night_light_changed {
+                    damage.push(Rectangle::from_size(tex.size()));
+                }
+                damage.extend(exempt_damage.iter().copied());
+
+                Ok(damage)
             })?;
 
         if result.is_ok() {
@@ -1309,45 +1467,15 @@ where
                 Kind::Unspecified,
             );
 
-            let postprocess_texture_shader = renderer
-                .glow_renderer_mut()
-                .egl_context()
-                .user_data()
-                .get::<PostprocessShader>()
-                .expect("OffscreenShader should be available through `init_shaders`");
             let texture_geometry =
                 texture_elem.geometry(output.current_scale().fractional_scale().into());
             let elements = {
-                let texture_elem = TextureShaderElement::new(
+                let texture_elem = PostprocessElement::new(
+                    &*renderer,
                     texture_elem,
-                    postprocess_texture_shader.0.clone(),
-                    vec![
-                        Uniform::new(
-                            "invert",
-                            if screen_filter.filter.inverted {
-                                1.
-                            } else {
-                                0.
-                            },
-                        ),
-                        Uniform::new(
-                            "color_mode",
-                            screen_filter
-                                .filter
-                                .color_filter
-                                .map(|val| val as u8 as f32)
-                                .unwrap_or(0.),
-                        ),
-                        Uniform::new(
-                            "night_light_level",
-                            if let Some(night_light) = screen_filter.night_light.as_ref() {
-                                let state = night_light.lock();
-                                if state.enabled { state.level as f32 } else { 0.0 }
-                            } else {
-                                0.0
-                            },
-                        ),
-                    ],
+                    &screen_filter.filter,
+                    night_light,
+                    state.night_light_exempt.clone(),
                 );
                 constrain_render_elements(
                     std::iter::once(texture_elem),
@@ -1402,6 +1530,7 @@ where
                     session.user_data().get::<SessionData>().unwrap(),
                     frame,
                     output.current_transform(),
+                    capture_tint.clone(),
                     |buffer, renderer, offscreen, dt, age, additional_damage| {
                         let old_len = if !additional_damage.is_empty() {
                             let area = output
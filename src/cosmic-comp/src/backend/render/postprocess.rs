@@ -0,0 +1,471 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::borrow::BorrowMut;
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                Element, Id, Kind, RenderElement, UnderlyingStorage, texture::TextureRenderElement,
+            },
+            gles::{GlesError, GlesFrame, GlesTexProgram, GlesTexture, Uniform, UniformValue},
+            glow::{GlowFrame, GlowRenderer},
+            utils::{CommitCounter, DamageSet, OpaqueRegions},
+        },
+    },
+    utils::{Buffer, Physical, Rectangle, Scale, Size, Transform},
+};
+
+use super::{PostprocessShader, element::AsGlowRenderer};
+use crate::{
+    config::{ColorFilter, ScreenFilter},
+    night_light::Adaptation,
+};
+
+/// sRGB to LMS cone responses
+const RGB_TO_LMS: [[f32; 3]; 3] = [
+    [17.8824, 43.5161, 4.11935],
+    [3.45565, 27.1554, 3.86714],
+    [0.0299566, 0.184309, 1.46709],
+];
+const LMS_TO_RGB: [[f32; 3]; 3] = [
+    [0.0809444479, -0.130504409, 0.116721066],
+    [-0.0102485335, 0.0540193266, -0.113614708],
+    [-0.000365296938, -0.00412161469, 0.693511405],
+];
+/// Spreads the colors lost to a deficiency over the channels that can still be told apart
+const DALTONIZE_SHIFT: [[f32; 3]; 3] = [[0., 0., 0.], [0.7, 1., 0.], [0.7, 0., 1.]];
+const IDENTITY: [[f32; 3]; 3] = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+
+fn mul(a: [[f32; 3]; 3], b: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    std::array::from_fn(|row| {
+        std::array::from_fn(|col| (0..3).map(|i| a[row][i] * b[i][col]).sum())
+    })
+}
+
+fn sub(a: [[f32; 3]; 3], b: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    std::array::from_fn(|row| std::array::from_fn(|col| a[row][col] - b[row][col]))
+}
+
+fn column_major(matrix: [[f32; 3]; 3]) -> [f32; 9] {
+    std::array::from_fn(|i| matrix[i % 3][i / 3])
+}
+
+/// Color filter as a single linear transform of (un-multiplied, sRGB encoded) colors.
+///
+/// The shader and the CPU pass both apply this and clamp the result,
+/// so the two can't disagree about a filter.
+pub fn color_filter_matrix(filter: Option<ColorFilter>) -> [[f32; 3]; 3] {
+    let simulate = match filter {
+        None => return IDENTITY,
+        Some(ColorFilter::Greyscale) => return [[1. / 3.; 3]; 3],
+        // what a color blind person sees, in LMS space
+        Some(ColorFilter::Protanopia) => [[0., 2.02344, -2.52581], [0., 1., 0.], [0., 0., 1.]],
+        Some(ColorFilter::Deuteranopia) => [[1., 0., 0.], [0.494207, 0., 1.24827], [0., 0., 1.]],
+        Some(ColorFilter::Tritanopia) => [[1., 0., 0.], [0., 1., 0.], [-0.395913, 0.801109, 0.]],
+    };
+    // color + shift * (color - simulated color)
+    let simulated = mul(LMS_TO_RGB, mul(simulate, RGB_TO_LMS));
+    let shift = mul(DALTONIZE_SHIFT, sub(IDENTITY, simulated));
+    std::array::from_fn(|row| std::array::from_fn(|col| IDENTITY[row][col] + shift[row][col]))
+}
+
+/// Inverts and filters an un-multiplied color, in the same order as the shader.
+pub fn filter_color(mut rgb: [f32; 3], filter: &ScreenFilter) -> [f32; 3] {
+    if filter.inverted {
+        rgb = rgb.map(|value| 1. - value);
+    }
+    if filter.color_filter.is_none() {
+        return rgb;
+    }
+
+    let matrix = color_filter_matrix(filter.color_filter);
+    matrix.map(|row| (0..3).map(|i| row[i] * rgb[i]).sum::<f32>().clamp(0., 1.))
+}
+
+/// Uniforms of the [`PostprocessShader`] applying `filter` and `night_light`
+pub fn uniforms(filter: &ScreenFilter, night_light: Adaptation) -> Vec<Uniform<'static>> {
+    vec![
+        Uniform::new("invert", if filter.inverted { 1. } else { 0. }),
+        Uniform::new(
+            "color_mode",
+            filter
+                .color_filter
+                .map(|val| val as u8 as f32)
+                .unwrap_or(0.),
+        ),
+        Uniform::new(
+            "color_matrix",
+            UniformValue::Matrix3x3 {
+                matrices: vec![column_major(color_filter_matrix(filter.color_filter))],
+                // GLES 2 doesn't allow transposing
+                transpose: false,
+            },
+        ),
+        Uniform::new("night_light_color", night_light.gains),
+        Uniform::new("night_light_brightness", night_light.brightness),
+        Uniform::new(
+            "night_light_preserve_luminance",
+            if night_light.preserve_luminance {
+                1.
+            } else {
+                0.
+            },
+        ),
+    ]
+}
+
+/// Draws an offscreen texture through the [`PostprocessShader`].
+///
+/// Exempt regions keep the screen filter, but skip the night light,
+/// so the windows shown there keep their true colors.
+#[derive(Debug)]
+pub struct PostprocessElement {
+    inner: TextureRenderElement<GlesTexture>,
+    program: GlesTexProgram,
+    uniforms: Vec<Uniform<'static>>,
+    exempt_uniforms: Vec<Uniform<'static>>,
+    /// Buffer coordinates of the texture
+    exempt: Vec<Rectangle<i32, Buffer>>,
+}
+
+impl PostprocessElement {
+    pub fn new<R: AsGlowRenderer>(
+        renderer: &R,
+        elem: TextureRenderElement<GlesTexture>,
+        filter: &ScreenFilter,
+        night_light: Adaptation,
+        exempt: Vec<Rectangle<i32, Buffer>>,
+    ) -> Self {
+        PostprocessElement {
+            inner: elem,
+            program: PostprocessShader::get(renderer),
+            uniforms: uniforms(filter, night_light),
+            exempt_uniforms: uniforms(filter, Adaptation::NEUTRAL),
+            exempt: if night_light.is_neutral() {
+                Vec::new()
+            } else {
+                exempt
+            },
+        }
+    }
+
+    /// Maps the exempt regions into the space of `dst`, as damage is given relative to it
+    fn exempt_regions(
+        &self,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+    ) -> Vec<Rectangle<i32, Physical>> {
+        let scale_x = dst.size.w as f64 / src.size.w;
+        let scale_y = dst.size.h as f64 / src.size.h;
+        self.exempt
+            .iter()
+            .filter_map(|rect| {
+                let rect = rect.to_f64().intersection(src)?;
+                Some(
+                    Rectangle::<f64, Physical>::new(
+                        (
+                            (rect.loc.x - src.loc.x) * scale_x,
+                            (rect.loc.y - src.loc.y) * scale_y,
+                        )
+                            .into(),
+                        (rect.size.w * scale_x, rect.size.h * scale_y).into(),
+                    )
+                    .to_i32_up(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Element for PostprocessElement {
+    fn id(&self) -> &Id {
+        self.inner.id()
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.inner.current_commit()
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        self.inner.src()
+    }
+
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.inner.geometry(scale)
+    }
+
+    fn transform(&self) -> Transform {
+        self.inner.transform()
+    }
+
+    fn damage_since(
+        &self,
+        scale: Scale<f64>,
+        commit: Option<CommitCounter>,
+    ) -> DamageSet<i32, Physical> {
+        self.inner.damage_since(scale, commit)
+    }
+
+    fn opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        self.inner.opaque_regions(scale)
+    }
+
+    fn alpha(&self) -> f32 {
+        self.inner.alpha()
+    }
+
+    fn kind(&self) -> Kind {
+        self.inner.kind()
+    }
+}
+
+impl RenderElement<GlowRenderer> for PostprocessElement {
+    fn draw(
+        &self,
+        frame: &mut GlowFrame<'_, '_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        let exempt = self.exempt_regions(src, dst);
+        // split the damage, so every pixel is drawn exactly once with either set of uniforms
+        let tinted = Rectangle::subtract_rects_many(damage.iter().copied(), exempt.iter().copied());
+        let untinted = damage
+            .iter()
+            .flat_map(|rect| {
+                exempt
+                    .iter()
+                    .filter_map(|exempt| rect.intersection(*exempt))
+            })
+            .collect::<Vec<_>>();
+
+        for (uniforms, damage) in [(&self.uniforms, tinted), (&self.exempt_uniforms, untinted)] {
+            if damage.is_empty() {
+                continue;
+            }
+            BorrowMut::<GlesFrame>::borrow_mut(&mut *frame)
+                .override_default_tex_program(self.program.clone(), uniforms.clone());
+            let res = RenderElement::<GlowRenderer>::draw(
+                &self.inner,
+                frame,
+                src,
+                dst,
+                &damage,
+                opaque_regions,
+            );
+            BorrowMut::<GlesFrame>::borrow_mut(&mut *frame).clear_tex_program_override();
+            res?;
+        }
+
+        Ok(())
+    }
+
+    fn underlying_storage(&self, _renderer: &mut GlowRenderer) -> Option<UnderlyingStorage<'_>> {
+        // the shader changes the contents, they can't be scanned out directly
+        None
+    }
+}
+
+/// CPU version of the [`PostprocessShader`], for pixels that are already in memory.
+///
+/// Transforms the damaged parts of a premultiplied 8-bit RGB image in place,
+/// which keeps the cost proportional to what changed.
+pub fn postprocess_pixels(
+    data: &mut [u8],
+    format: Fourcc,
+    size: Size<i32, Buffer>,
+    stride: usize,
+    damage: &[Rectangle<i32, Buffer>],
+    filter: &ScreenFilter,
+    night_light: Adaptation,
+) -> anyhow::Result<()> {
+    // byte offsets of red, green and blue in a pixel
+    let channels = match format {
+        Fourcc::Argb8888 | Fourcc::Xrgb8888 => [2, 1, 0],
+        Fourcc::Abgr8888 | Fourcc::Xbgr8888 => [0, 1, 2],
+        _ => anyhow::bail!("Unsupported pixel format {format:?}"),
+    };
+    let alpha = matches!(format, Fourcc::Argb8888 | Fourcc::Abgr8888).then_some(3);
+
+    // night light alone works per channel, so opaque pixels can go through a lookup table
+    let lut = (filter.is_noop() && !night_light.preserve_luminance).then(|| {
+        [0, 1, 2].map(|channel| {
+            let mut lut = [0u8; 256];
+            for (value, entry) in lut.iter_mut().enumerate() {
+                *entry = (night_light.apply_channel(channel, value as f32 / 255.) * 255.)
+                    .round()
+                    .clamp(0., 255.) as u8;
+            }
+            lut
+        })
+    });
+
+    let bounds = Rectangle::from_size(size);
+    for rect in damage.iter().filter_map(|rect| rect.intersection(bounds)) {
+        for y in rect.loc.y..rect.loc.y + rect.size.h {
+            let row = y as usize * stride;
+            let start = row + rect.loc.x as usize * 4;
+            let end = row + (rect.loc.x + rect.size.w) as usize * 4;
+            let Some(pixels) = data.get_mut(start..end) else {
+                continue;
+            };
+
+            for pixel in pixels.chunks_exact_mut(4) {
+                let a = alpha.map(|i| pixel[i]).unwrap_or(u8::MAX);
+                if let Some(lut) = lut.as_ref().filter(|_| a == u8::MAX) {
+                    for (i, channel) in channels.iter().enumerate() {
+                        pixel[*channel] = lut[i][pixel[*channel] as usize];
+                    }
+                    continue;
+                }
+
+                let a = a as f32 / 255.;
+                if a == 0. {
+                    continue;
+                }
+                // un-multiply
+                let rgb = channels.map(|i| pixel[i] as f32 / 255. / a);
+                let rgb = night_light.apply(filter_color(rgb, filter));
+                for (i, channel) in channels.iter().enumerate() {
+                    // re-multiply
+                    pixel[*channel] = (rgb[i] * a * 255.).round().clamp(0., 255.) as u8;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use smithay::{
+        backend::{
+            allocator::Fourcc,
+            renderer::gles::{Uniform, UniformValue},
+        },
+        utils::{Rectangle, Size},
+    };
+
+    use super::{filter_color, postprocess_pixels, uniforms};
+    use crate::{
+        config::{ColorFilter, ScreenFilter},
+        night_light::Adaptation,
+    };
+
+    const COLORS: [[f32; 3]; 5] = [
+        [1., 0., 0.],
+        [0., 1., 0.],
+        [0., 0., 1.],
+        [0.8, 0.4, 0.1],
+        [0.5, 0.5, 0.5],
+    ];
+    const FILTERS: [ColorFilter; 4] = [
+        ColorFilter::Greyscale,
+        ColorFilter::Protanopia,
+        ColorFilter::Deuteranopia,
+        ColorFilter::Tritanopia,
+    ];
+
+    /// The filters spelled out step by step, which both paths have to match.
+    fn reference([r, g, b]: [f32; 3], filter: ColorFilter) -> [f32; 3] {
+        let l = 17.8824 * r + 43.5161 * g + 4.11935 * b;
+        let m = 3.45565 * r + 27.1554 * g + 3.86714 * b;
+        let s = 0.0299566 * r + 0.184309 * g + 1.46709 * b;
+        let (l, m, s) = match filter {
+            ColorFilter::Greyscale => return [(r + g + b) / 3.; 3],
+            ColorFilter::Protanopia => (2.02344 * m - 2.52581 * s, m, s),
+            ColorFilter::Deuteranopia => (l, 0.494207 * l + 1.24827 * s, s),
+            ColorFilter::Tritanopia => (l, m, -0.395913 * l + 0.801109 * m),
+        };
+        let simulated = [
+            0.0809444479 * l - 0.130504409 * m + 0.116721066 * s,
+            -0.0102485335 * l + 0.0540193266 * m - 0.113614708 * s,
+            -0.000365296938 * l - 0.00412161469 * m + 0.693511405 * s,
+        ];
+        let diff = [r - simulated[0], g - simulated[1], b - simulated[2]];
+        [r, g + diff[0] * 0.7 + diff[1], b + diff[0] * 0.7 + diff[2]].map(|v| v.clamp(0., 1.))
+    }
+
+    /// What `offscreen.frag` does with the uniforms: `clamp(color_matrix * color.rgb, 0.0, 1.0)`
+    fn shader(uniforms: &[Uniform<'_>], rgb: [f32; 3]) -> [f32; 3] {
+        let color_mode = uniforms.iter().find(|u| u.name == "color_mode").unwrap();
+        if matches!(color_mode.value, UniformValue::_1f(mode) if mode == 0.) {
+            return rgb;
+        }
+        let matrix = uniforms.iter().find(|u| u.name == "color_matrix").unwrap();
+        let UniformValue::Matrix3x3 {
+            matrices,
+            transpose: false,
+        } = &matrix.value
+        else {
+            panic!("unexpected color_matrix {:?}", matrix.value);
+        };
+        // column major
+        std::array::from_fn(|row| {
+            (0..3)
+                .map(|col| matrices[0][col * 3 + row] * rgb[col])
+                .sum::<f32>()
+                .clamp(0., 1.)
+        })
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-3), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn test_filters_match_reference() {
+        for filter in FILTERS {
+            let screen_filter = ScreenFilter {
+                inverted: false,
+                color_filter: Some(filter),
+            };
+            let uniforms = uniforms(&screen_filter, Adaptation::NEUTRAL);
+            for color in COLORS {
+                let expected = reference(color, filter);
+                assert_close(filter_color(color, &screen_filter), expected);
+                assert_close(shader(&uniforms, color), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_postprocess_pixels_damage() {
+        // two opaque pixels, only the first one is damaged
+        let mut data = [204, 102, 26, 255, 204, 102, 26, 255];
+        let inverted = ScreenFilter {
+            inverted: true,
+            color_filter: None,
+        };
+        postprocess_pixels(
+            &mut data,
+            Fourcc::Abgr8888,
+            Size::from((2, 1)),
+            8,
+            &[Rectangle::new((0, 0).into(), (1, 1).into())],
+            &inverted,
+            Adaptation::NEUTRAL,
+        )
+        .unwrap();
+        assert_eq!(data, [51, 153, 229, 255, 204, 102, 26, 255]);
+    }
+
+    #[test]
+    fn test_no_filter() {
+        let screen_filter = ScreenFilter::default();
+        let uniforms = uniforms(&screen_filter, Adaptation::NEUTRAL);
+        for color in COLORS {
+            assert_eq!(filter_color(color, &screen_filter), color);
+            assert_eq!(shader(&uniforms, color), color);
+        }
+        let inverted = ScreenFilter {
+            inverted: true,
+            color_filter: None,
+        };
+        assert_close(filter_color([0.8, 0.4, 0.1], &inverted), [0.2, 0.6, 0.9]);
+    }
+}
@@ -22,9 +22,49 @@ uniform float tint;
 
 uniform float invert;
 uniform float color_mode;
+// greyscale or color blindness filter, see `postprocess::color_filter_matrix`
+uniform mat3 color_matrix;
+
+// night light white point, sRGB encoded
+uniform vec3 night_light_color;
+uniform float night_light_preserve_luminance;
+// linear light multiplier of the extra dim
+uniform float night_light_brightness;
+
+// keep in sync with `night_light::Adaptation`
+const vec3 LUMA = vec3(0.2126, 0.7152, 0.0722);
+const float MAX_COMPENSATION = 3.0;
+
+vec3 srgb_to_linear(vec3 color) {
+    vec3 low = color / 12.92;
+    vec3 high = pow((color + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, step(color, vec3(0.04045)));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    vec3 low = color * 12.92;
+    vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return mix(high, low, step(color, vec3(0.0031308)));
+}
+
+vec3 night_light(vec3 color) {
+    vec3 linear = srgb_to_linear(clamp(color, 0.0, 1.0));
+    vec3 gains = srgb_to_linear(night_light_color);
+    vec3 adapted = linear * gains;
+
+    if (night_light_preserve_luminance == 1.0) {
+        float compensation = min(1.0 / max(dot(gains, LUMA), 0.0001) - 1.0, MAX_COMPENSATION);
+        float darkness = 1.0 - clamp(dot(linear, LUMA), 0.0, 1.0);
+        adapted *= 1.0 + compensation * darkness * darkness;
 
-// ELITE NIGHT LIGHT: New uniform
-uniform float night_light_level;
+        float peak = max(adapted.r, max(adapted.g, adapted.b));
+        if (peak > 1.0) {
+            adapted /= peak;
+        }
+    }
+
+    return linear_to_srgb(adapted * night_light_brightness);
+}
 
 void main() {
     vec4 color = texture2D(tex, v_coords);
@@ -44,25 +84,12 @@ void main() {
         color.rgb = 1.0 - color.rgb;
     }
 
-    if (color_mode == 1.0) {        // greyscale
-        float value = (color.r + color.g + color.b) / 3.0;
-        color = vec4(value, value, value, color.a);
-    } else if (color_mode >= 2.0) {
-        // ... (existing color blind filters)
+    if (color_mode >= 1.0) {
+        color.rgb = clamp(color_matrix * color.rgb, 0.0, 1.0);
     }
 
-    // ELITE NIGHT LIGHT: Apply orange tint at the very end
-    // Using 0.5 as threshold to be safe against undefined/noisy values
-    if (night_light_level > 0.5) {
-        vec3 orange_tint;
-        if (night_light_level < 1.5) {
-            orange_tint = vec3(1.0, 0.9, 0.8); // Soft (1.0)
-        } else if (night_light_level < 2.5) {
-            orange_tint = vec3(1.0, 0.8, 0.6); // Warm (2.0)
-        } else {
-            orange_tint = vec3(1.0, 0.7, 0.4); // Strong (3.0)
-        }
-        color.rgb *= orange_tint;
+    if (night_light_color != vec3(1.0) || night_light_brightness != 1.0) {
+        color.rgb = night_light(color.rgb);
     }
 
     // re-multiply
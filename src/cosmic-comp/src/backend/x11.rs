@@ -197,6 +197,15 @@ impl X11State {
         }
         Ok(())
     }
+
+    pub fn update_night_light(&mut self) {
+        for surface in &mut self.surfaces {
+            surface.dirty = true;
+            if !surface.pending {
+                surface.render.ping();
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
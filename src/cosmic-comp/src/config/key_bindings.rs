@@ -1,6 +1,11 @@
 use cosmic_settings_config::shortcuts::State as KeyState;
 use cosmic_settings_config::shortcuts::{self, Modifiers};
 use smithay::input::keyboard::ModifiersState;
+use std::{
+    ffi::OsStr,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Action {
@@ -19,6 +24,70 @@ pub enum PrivateAction {
         shortcuts::action::ResizeEdge,
         shortcuts::State,
     ),
+    NightLight(NightLightAction),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NightLightAction {
+    Toggle,
+    Warmer,
+    Cooler,
+    /// Steps through the intensity presets
+    CyclePresets,
+}
+
+/// Where `install.sh` puts `night-light-ctl` and the `toggle-night-mode` shim.
+const NIGHT_LIGHT_INSTALL_DIR: &str = "/usr/local/bin";
+
+impl NightLightAction {
+    /// Recognizes custom shortcuts that run the installed `night-light-ctl`,
+    /// so they run in the compositor without spawning anything.
+    ///
+    /// Anything else, including wrappers or other programs of the same name, is spawned as usual.
+    pub fn from_command(command: &str) -> Option<Self> {
+        let path = std::env::var_os("PATH");
+        Self::from_installed_command(command, path.as_deref(), Path::new(NIGHT_LIGHT_INSTALL_DIR))
+    }
+
+    fn from_installed_command(
+        command: &str,
+        path: Option<&OsStr>,
+        install_dir: &Path,
+    ) -> Option<Self> {
+        let mut args = command.split_whitespace();
+        let program = args.next()?;
+        let name = program.rsplit('/').next()?;
+        let action = match (name, args.next()) {
+            ("night-light-ctl", Some("toggle")) => NightLightAction::Toggle,
+            ("night-light-ctl", Some("warmer")) => NightLightAction::Warmer,
+            ("night-light-ctl", Some("cooler")) => NightLightAction::Cooler,
+            ("night-light-ctl", Some("cycle")) => NightLightAction::CyclePresets,
+            // the script night-light-ctl replaced, still bound on older installs
+            ("toggle-night-mode", None) => NightLightAction::Toggle,
+            _ => return None,
+        };
+        if args.next().is_some() {
+            return None;
+        }
+
+        let resolved = resolve_program(program, path)?.canonicalize().ok()?;
+        let installed = install_dir.join(name).canonicalize().ok()?;
+        (resolved == installed).then_some(action)
+    }
+}
+
+/// The file `/bin/sh` would run for `program`.
+fn resolve_program(program: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(PathBuf::from(program));
+    }
+    std::env::split_paths(path?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| {
+            candidate
+                .metadata()
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        })
 }
 
 /// Convert `cosmic_settings_config::shortcuts::State` to `smithay::backend::input::KeyState`.
@@ -54,3 +123,85 @@ pub fn cosmic_modifiers_from_smithay(value: ModifiersState) -> Modifiers {
         logo: value.logo,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::NightLightAction::{self, Cooler, CyclePresets, Toggle, Warmer};
+    use std::{ffi::OsStr, fs, os::unix::fs::PermissionsExt, path::Path};
+
+    fn executable(path: &Path) {
+        fs::write(path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_night_light_from_command() {
+        let root =
+            std::env::temp_dir().join(format!("night-light-shortcuts-{}", std::process::id()));
+        let install_dir = root.join("install");
+        let wrapper_dir = root.join("wrapper");
+        fs::create_dir_all(&install_dir).unwrap();
+        fs::create_dir_all(&wrapper_dir).unwrap();
+        executable(&install_dir.join("night-light-ctl"));
+        executable(&install_dir.join("toggle-night-mode"));
+        executable(&wrapper_dir.join("night-light-ctl"));
+        // not executable, so skipped in the lookup
+        fs::write(wrapper_dir.join("toggle-night-mode"), "").unwrap();
+
+        let path = std::env::join_paths([&wrapper_dir, &install_dir]).unwrap();
+        let installed_path = install_dir.clone().into_os_string();
+        let action = |command: &str, path: &OsStr| {
+            NightLightAction::from_installed_command(command, Some(path), &install_dir)
+        };
+
+        assert_eq!(
+            action("night-light-ctl toggle", &installed_path),
+            Some(Toggle)
+        );
+        assert_eq!(
+            action("night-light-ctl  warmer ", &installed_path),
+            Some(Warmer)
+        );
+        assert_eq!(
+            action("night-light-ctl cooler", &installed_path),
+            Some(Cooler)
+        );
+        assert_eq!(
+            action("night-light-ctl cycle", &installed_path),
+            Some(CyclePresets)
+        );
+        assert_eq!(action("toggle-night-mode", &path), Some(Toggle));
+        let absolute = format!("{} toggle", install_dir.join("night-light-ctl").display());
+        assert_eq!(action(&absolute, &path), Some(Toggle));
+
+        // a wrapper of the same name comes first in the PATH, or is called directly
+        assert_eq!(action("night-light-ctl toggle", &path), None);
+        let wrapper = format!("{} toggle", wrapper_dir.join("night-light-ctl").display());
+        assert_eq!(action(&wrapper, &installed_path), None);
+        assert_eq!(
+            action("~/bin/night-light-ctl toggle", &installed_path),
+            None
+        );
+
+        // anything else is spawned as usual
+        assert_eq!(
+            action("night-light-ctl set --level 2", &installed_path),
+            None
+        );
+        assert_eq!(
+            action("night-light-ctl toggle --verbose", &installed_path),
+            None
+        );
+        assert_eq!(action("toggle-night-mode on", &installed_path), None);
+        assert_eq!(action("night-light-ctl status", &installed_path), None);
+        assert_eq!(action("night-light-ctl", &installed_path), None);
+        assert_eq!(action("my-night-light-ctl toggle", &installed_path), None);
+        assert_eq!(
+            action("sh -c 'night-light-ctl toggle'", &installed_path),
+            None
+        );
+        assert_eq!(action("", &installed_path), None);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,6 +1,11 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use crate::{
+    night_light::{
+        capture::CaptureSource,
+        content::ContentKind,
+        schedule::{DEFAULT_SCHEDULE_FROM, DEFAULT_SCHEDULE_TO, ScheduleMode},
+    },
     shell::Shell,
     state::{BackendData, State},
     utils::prelude::OutputExt,
@@ -53,7 +58,7 @@ use cosmic_comp_config::{
     },
     workspace::WorkspaceConfig,
 };
-pub use key_bindings::{Action, PrivateAction};
+pub use key_bindings::{Action, NightLightAction, PrivateAction};
 use types::WlXkbConfig;
 
 #[derive(Debug)]
@@ -68,6 +73,8 @@ pub struct Config {
     pub shortcuts: Shortcuts,
     // Tiling exceptions from `com.system76.CosmicSettings.WindowRules`
     pub tiling_exceptions: Vec<ApplicationException>,
+    /// Windows shown without night light, from `com.system76.CosmicSettings.WindowRules`
+    pub night_light_exceptions: Vec<ApplicationException>,
     /// System actions from `com.system76.CosmicSettings.Shortcuts`
     pub system_actions: BTreeMap<shortcuts::action::System, String>,
 }
@@ -77,6 +84,7 @@ pub struct DynamicConfig {
     outputs: (Option<PathBuf>, OutputsConfig),
     numlock: (Option<PathBuf>, NumlockStateConfig),
     accessibility_filter: (Option<PathBuf>, ScreenFilter),
+    night_light: (Option<PathBuf>, NightLightConfig),
 }
 
 #[derive(Default, Debug, Deserialize, Serialize)]
@@ -167,6 +175,79 @@ pub enum ColorFilter {
     Tritanopia = 4,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct NightLightConfig {
+    pub enabled: bool,
+    pub temperature: u32,
+    pub transition_duration_ms: u32,
+    pub schedule_mode: ScheduleMode,
+    pub schedule_from: u16,
+    pub schedule_to: u16,
+    pub schedule_ramp: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<(f64, f64)>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<OutputNightLightConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tint_captures: Vec<CaptureSource>,
+    pub preserve_luminance: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pause_for_content: Vec<ContentKind>,
+    pub dim: f32,
+    /// What the schedule asked for last, so manual changes stick across restarts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled: Option<bool>,
+    #[serde(rename = "override", skip_serializing_if = "Option::is_none")]
+    pub timed_override: Option<NightLightOverrideConfig>,
+    /// Unix timestamp the two above were last valid at, transitions since then discard them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evaluated_at: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct NightLightOverrideConfig {
+    pub enabled: bool,
+    /// Unix timestamp the override ends at, `None` for the next schedule transition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<i64>,
+    pub previous: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct OutputNightLightConfig {
+    pub connector: String,
+    pub edid: Option<EdidProduct>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<u32>,
+}
+
+impl Default for NightLightConfig {
+    fn default() -> Self {
+        NightLightConfig {
+            enabled: false,
+            temperature: crate::night_light::LEVEL_PRESETS[1],
+            transition_duration_ms: crate::night_light::DEFAULT_TRANSITION_DURATION.as_millis()
+                as u32,
+            schedule_mode: ScheduleMode::default(),
+            schedule_from: DEFAULT_SCHEDULE_FROM,
+            schedule_to: DEFAULT_SCHEDULE_TO,
+            schedule_ramp: 0,
+            location: None,
+            outputs: Vec::new(),
+            tint_captures: Vec::new(),
+            preserve_luminance: false,
+            pause_for_content: Vec::new(),
+            dim: 0.,
+            scheduled: None,
+            timed_override: None,
+            evaluated_at: None,
+        }
+    }
+}
+
 impl Config {
     pub fn load(loop_handle: &LoopHandle<'_, State>) -> Config {
         let config = cosmic_config::Config::new("com.system76.CosmicComp", 1).unwrap();
@@ -282,6 +363,7 @@ impl Config {
         let window_rules_context =
             window_rules::context().expect("Failed to load window rules config");
         let tiling_exceptions = window_rules::tiling_exceptions(&window_rules_context);
+        let night_light_exceptions = night_light_exceptions(&window_rules_context);
 
         match cosmic_config::calloop::ConfigWatchSource::new(&window_rules_context) {
             Ok(source) => {
@@ -295,6 +377,13 @@ impl Config {
                                     state.common.config.tiling_exceptions.iter(),
                                 );
                             }
+                            NIGHT_LIGHT_EXCEPTIONS => {
+                                let new_exceptions = night_light_exceptions(&config);
+                                state.common.config.night_light_exceptions = new_exceptions;
+                                state.common.shell.write().update_night_light_exceptions(
+                                    state.common.config.night_light_exceptions.iter(),
+                                );
+                            }
                             _ => (),
                         }
                     }
@@ -331,6 +420,7 @@ impl Config {
             shortcuts,
             system_actions,
             tiling_exceptions,
+            night_light_exceptions,
         }
     }
 
@@ -345,13 +435,35 @@ impl Config {
             .ok();
         let filter = Self::load_filter_state(&filter_path);
 
+        let night_light_path = xdg.place_state_file("cosmic-comp/night_light.ron").ok();
+        let night_light = Self::load_night_light(&night_light_path);
+
         DynamicConfig {
             outputs: (output_path, outputs),
             numlock: (numlock_path, numlock),
             accessibility_filter: (filter_path, filter),
+            night_light: (night_light_path, night_light),
         }
     }
 
+    fn load_night_light(path: &Option<PathBuf>) -> NightLightConfig {
+        path.as_deref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                ron::de::from_reader::<_, NightLightConfig>(
+                    OpenOptions::new().read(true).open(path).unwrap(),
+                )
+                .map_err(|err| {
+                    warn!(?err, "Failed to read night_light.ron, resetting..");
+                    if let Err(err) = std::fs::remove_file(path) {
+                        error!(?err, "Failed to remove night_light.ron.");
+                    }
+                })
+                .ok()
+            })
+            .unwrap_or_default()
+    }
+
     fn load_numlock(path: &Option<PathBuf>) -> NumlockStateConfig {
         path.as_deref()
             .filter(|path| path.exists())
@@ -405,7 +517,7 @@ impl Config {
         loop_handle: &LoopHandle<'static, State>,
         workspace_state: &mut WorkspaceUpdateGuard<'_, State>,
         xdg_activation_state: &XdgActivationState,
-        night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+        night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
         startup_done: Arc<AtomicBool>,
         clock: &Clock<Monotonic>,
     ) -> anyhow::Result<()> {
@@ -739,6 +851,14 @@ impl DynamicConfig {
             &mut self.accessibility_filter.1,
         )
     }
+
+    pub fn night_light(&self) -> &NightLightConfig {
+        &self.night_light.1
+    }
+
+    pub fn night_light_mut(&mut self) -> PersistenceGuard<'_, NightLightConfig> {
+        PersistenceGuard(self.night_light.0.clone(), &mut self.night_light.1)
+    }
 }
 
 pub fn xkb_config_to_wl(config: &XkbConfig) -> WlXkbConfig<'_> {
@@ -751,6 +871,16 @@ pub fn xkb_config_to_wl(config: &XkbConfig) -> WlXkbConfig<'_> {
     }
 }
 
+/// Key of the "no night light" rules in `com.system76.CosmicSettings.WindowRules`
+const NIGHT_LIGHT_EXCEPTIONS: &str = "night_light_exception_custom";
+
+fn night_light_exceptions(config: &cosmic_config::Config) -> Vec<ApplicationException> {
+    // unset unless the user added rules
+    config
+        .get::<Vec<ApplicationException>>(NIGHT_LIGHT_EXCEPTIONS)
+        .unwrap_or_default()
+}
+
 fn get_config<T: Default + serde::de::DeserializeOwned>(
     config: &cosmic_config::Config,
     key: &str,
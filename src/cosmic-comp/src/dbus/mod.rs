@@ -86,7 +86,7 @@ pub fn init(
 
 /// Updated the D-Bus activation environment with `WAYLAND_DISPLAY` and
 /// `DISPLAY` variables.
-pub fn ready(common: &Common) -> Result<()> {
+pub fn ready(common: &mut Common) -> Result<()> {
     let conn = Connection::session()?;
     let proxy = DBusProxy::new(&conn)?;
 
@@ -104,13 +104,43 @@ pub fn ready(common: &Common) -> Result<()> {
 
     // ELITE NIGHT LIGHT: Register our custom DBus interface under a UNIQUE name
     let state = common.night_light.clone();
+    let shell = common.shell.clone();
+    let executor = common.async_executor.clone();
+    let (changed, rx) = calloop::channel::channel();
+    common
+        .event_loop_handle
+        .insert_source(rx, |event, _, state| {
+            if let calloop::channel::Event::Msg(()) = event {
+                state.update_night_light();
+            }
+        })
+        .map_err(|InsertError { error, .. }| error)
+        .with_context(|| "Failed to add night light channel to event_loop")?;
+    let (signals, signals_rx) = std::sync::mpsc::channel();
+    common.night_light_signals = Some(signals);
     std::thread::spawn(move || {
         // Sleep for 2 seconds to ensure we don't race with the session bus startup
         std::thread::sleep(std::time::Duration::from_secs(2));
         match Connection::session() {
             Ok(conn) => {
-                let interface = night_light::NightLightInterface { state };
-                if let Err(e) = conn.object_server().at("/io/github/kernel_ux/EliteNightLight", interface) {
+                match block_on(name_owners::NameOwners::new(conn.inner(), &executor)) {
+                    Ok(name_owners) => executor.spawn_ok({
+                        let conn = conn.inner().clone();
+                        let state = state.clone();
+                        let changed = changed.clone();
+                        async move {
+                            if let Err(err) =
+                                night_light::release_inhibitors(conn, name_owners, state, changed)
+                                    .await
+                            {
+                                warn!(?err, "Elite Night Light: Stopped watching inhibitors");
+                            }
+                        }
+                    }),
+                    Err(err) => warn!(?err, "Elite Night Light: Failed to track name owners"),
+                }
+                let interface = night_light::NightLightInterface::new(state, shell, changed);
+                if let Err(e) = conn.object_server().at(night_light::PATH, interface) {
                     error!("Elite Night Light: CRITICAL - Failed to export object: {}", e);
                 } else {
                     // Request a UNIQUE name that doesn't conflict with com.system76
@@ -120,8 +150,22 @@ pub fn ready(common: &Common) -> Result<()> {
                         info!("Elite Night Light: Unique D-Bus service registered successfully.");
                     }
                 }
-                // Keep the connection alive
-                loop { std::thread::park(); }
+                // Keep the connection alive and announce changes made by the compositor
+                match conn
+                    .object_server()
+                    .interface::<_, night_light::NightLightInterface>(night_light::PATH)
+                {
+                    Ok(iface) => {
+                        for () in signals_rx {
+                            if let Err(err) = night_light::publish_changes(&iface) {
+                                warn!(?err, "Elite Night Light: Failed to emit PropertiesChanged");
+                            }
+                        }
+                    }
+                    Err(_) => loop {
+                        std::thread::park();
+                    },
+                }
             }
             Err(e) => error!("Elite Night Light: CRITICAL - Failed to connect to session bus: {}", e),
         }
@@ -125,7 +125,6 @@ impl NameOwners {
         Ok(NameOwners(inner))
     }
 
-    #[allow(dead_code)]
     pub fn has_unique_name(&self, name: &UniqueName<'_>) -> bool {
         let mut inner = self.0.lock().unwrap();
         inner.update_if_needed();
@@ -1,45 +1,635 @@
-use zbus::interface;
-use std::sync::Arc;
-use parking_lot::Mutex;
-
-#[derive(Debug, Clone)]
-pub struct NightLightState {
-    pub enabled: bool,
-    pub level: u8,
+use crate::{
+    night_light::{
+        LEVEL_PRESETS, MAX_DIM, NightLightState, OutputKey, OverrideExpiry, TimedOverride,
+        capture::CaptureSource,
+        content::ContentKind,
+        schedule::{MAX_SCHEDULE_RAMP, ScheduleMode},
+    },
+    shell::Shell,
+};
+use futures_executor::block_on;
+use futures_util::StreamExt;
+use parking_lot::{Mutex, RwLock};
+use smithay::output::Output;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+use time::OffsetDateTime;
+use zbus::{
+    blocking::object_server::InterfaceRef, fdo, interface, message::Header, names::UniqueName,
+};
+
+use super::name_owners::NameOwners;
+
+pub const PATH: &str = "/io/github/kernel_ux/EliteNightLight";
+
+pub struct NightLightInterface {
+    state: Arc<Mutex<NightLightState>>,
+    shell: Arc<RwLock<Shell>>,
+    /// Wakes the main loop, so the backend picks up the change
+    changed: calloop::channel::Sender<()>,
+    /// Property values last announced on the bus
+    published: Published,
 }
 
-impl NightLightState {
-    pub fn new() -> Self {
-        Self {
-            enabled: false,
-            level: 2,
+#[derive(Debug, Clone, PartialEq)]
+struct Published {
+    enabled: bool,
+    level: u8,
+    temperature: u32,
+    transition_duration: Duration,
+    schedule_mode: ScheduleMode,
+    schedule_from: u16,
+    schedule_to: u16,
+    schedule_ramp: u16,
+    location: Option<(f64, f64)>,
+    tint_captures: BTreeSet<CaptureSource>,
+    preserve_luminance: bool,
+    dim: f32,
+    pause_for: BTreeSet<ContentKind>,
+    suspended: BTreeMap<OutputKey, ContentKind>,
+    inhibited: bool,
+    scheduled: Option<bool>,
+    timed_override: Option<TimedOverride>,
+}
+
+impl From<&NightLightState> for Published {
+    fn from(state: &NightLightState) -> Self {
+        Published {
+            enabled: state.enabled,
+            level: state.level(),
+            temperature: state.temperature,
+            transition_duration: state.transition_duration,
+            schedule_mode: state.schedule_mode,
+            schedule_from: state.schedule_from,
+            schedule_to: state.schedule_to,
+            schedule_ramp: state.schedule_ramp,
+            location: state.location,
+            tint_captures: state.tint_captures.clone(),
+            preserve_luminance: state.preserve_luminance,
+            dim: state.dim,
+            pause_for: state.pause_for.clone(),
+            suspended: state.suspended().clone(),
+            inhibited: state.is_inhibited(),
+            scheduled: state.scheduled(),
+            timed_override: state.timed_override().copied(),
         }
     }
 }
 
-pub struct NightLightInterface {
-    pub state: Arc<Mutex<NightLightState>>,
+impl NightLightInterface {
+    pub fn new(
+        state: Arc<Mutex<NightLightState>>,
+        shell: Arc<RwLock<Shell>>,
+        changed: calloop::channel::Sender<()>,
+    ) -> Self {
+        let published = Published::from(&*state.lock());
+        NightLightInterface {
+            state,
+            shell,
+            changed,
+            published,
+        }
+    }
+
+    /// Records what a setter changed, zbus already emits `PropertiesChanged` for that property.
+    fn notify(&mut self, update: impl FnOnce(&mut Published, &NightLightState)) {
+        update(&mut self.published, &self.state.lock());
+        let _ = self.changed.send(());
+    }
+
+    fn set_override(&mut self, enabled: bool, expiry: OverrideExpiry) {
+        self.state.lock().set_override(enabled, expiry);
+        // not a property setter, leave `Override` and `Enabled` to `publish_changes`
+        let _ = self.changed.send(());
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        self.shell.read().outputs().cloned().collect()
+    }
+
+    fn output(&self, connector: &str) -> fdo::Result<OutputKey> {
+        self.outputs()
+            .iter()
+            .find(|output| output.name() == connector)
+            .map(OutputKey::from)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Unknown output {connector}")))
+    }
+}
+
+/// Emits `PropertiesChanged` for everything that changed since the last call,
+/// no matter if the change came from a client, the schedule or the compositor itself.
+pub fn publish_changes(iface: &InterfaceRef<NightLightInterface>) -> zbus::Result<()> {
+    let mut interface = iface.get_mut();
+    let current = Published::from(&*interface.state.lock());
+    let previous = std::mem::replace(&mut interface.published, current.clone());
+    let emitter = iface.signal_emitter();
+
+    block_on(async {
+        if previous.enabled != current.enabled {
+            interface.enabled_changed(emitter).await?;
+        }
+        if previous.level != current.level {
+            interface.level_changed(emitter).await?;
+        }
+        if previous.temperature != current.temperature {
+            interface.temperature_changed(emitter).await?;
+        }
+        if previous.transition_duration != current.transition_duration {
+            interface.transition_duration_changed(emitter).await?;
+        }
+        if previous.schedule_mode != current.schedule_mode {
+            interface.schedule_mode_changed(emitter).await?;
+        }
+        if previous.schedule_from != current.schedule_from {
+            interface.schedule_from_changed(emitter).await?;
+        }
+        if previous.schedule_to != current.schedule_to {
+            interface.schedule_to_changed(emitter).await?;
+        }
+        if previous.schedule_ramp != current.schedule_ramp {
+            interface.schedule_ramp_changed(emitter).await?;
+        }
+        if previous.location != current.location {
+            interface.location_changed(emitter).await?;
+        }
+        if previous.tint_captures != current.tint_captures {
+            interface.tint_captures_changed(emitter).await?;
+        }
+        if previous.preserve_luminance != current.preserve_luminance {
+            interface.preserve_luminance_changed(emitter).await?;
+        }
+        if previous.dim != current.dim {
+            interface.dim_changed(emitter).await?;
+        }
+        if previous.pause_for != current.pause_for {
+            interface.pause_for_content_changed(emitter).await?;
+        }
+        if previous.suspended != current.suspended {
+            interface.suspend_reasons_changed(emitter).await?;
+        }
+        if previous.inhibited != current.inhibited {
+            interface.inhibited_changed(emitter).await?;
+        }
+        if previous.scheduled != current.scheduled {
+            interface.scheduled_changed(emitter).await?;
+        }
+        if previous.timed_override != current.timed_override {
+            interface.override_changed(emitter).await?;
+            interface.override_expiry_changed(emitter).await?;
+        }
+        Ok(())
+    })
+}
+
+/// Ends the inhibits of clients that left the bus, so a crashed client doesn't keep night light off.
+pub async fn release_inhibitors(
+    conn: zbus::Connection,
+    name_owners: NameOwners,
+    state: Arc<Mutex<NightLightState>>,
+    changed: calloop::channel::Sender<()>,
+) -> zbus::Result<()> {
+    let dbus = fdo::DBusProxy::new(&conn).await?;
+    let mut owner_changes = dbus.receive_name_owner_changed().await?;
+    while owner_changes.next().await.is_some() {
+        let released = state.lock().retain_inhibitors(|owner| {
+            UniqueName::try_from(owner).is_ok_and(|owner| name_owners.has_unique_name(&owner))
+        });
+        if released && changed.send(()).is_err() {
+            break;
+        }
+    }
+    Ok(())
 }
 
 #[interface(name = "io.github.kernel_ux.EliteNightLight.Manager")]
 impl NightLightInterface {
-    #[zbus(name = "Enabled")]
+    #[zbus(property, name = "Enabled")]
     fn enabled(&self) -> bool {
         self.state.lock().enabled
     }
 
-    #[zbus(name = "Level")]
+    #[zbus(property, name = "Enabled")]
+    fn set_enabled(&mut self, enabled: bool) {
+        self.state.lock().set_enabled_by_hand(enabled);
+        self.notify(|published, state| published.enabled = state.enabled);
+    }
+
+    /// Preset intensity 1 to 3, or `0` for a custom temperature.
+    #[zbus(property, name = "Level")]
     fn level(&self) -> u8 {
-        self.state.lock().level
+        self.state.lock().level()
     }
 
-    #[zbus(name = "SetEnabled")]
-    fn set_enabled(&mut self, enabled: bool) {
-        self.state.lock().enabled = enabled;
+    /// Temperatures of the levels 1 to 3 in Kelvin.
+    #[zbus(property(emits_changed_signal = "const"), name = "LevelTemperatures")]
+    fn level_temperatures(&self) -> Vec<u32> {
+        LEVEL_PRESETS.to_vec()
+    }
+
+    #[zbus(property, name = "Level")]
+    fn set_level(&mut self, level: u8) -> fdo::Result<()> {
+        if !self.state.lock().set_level(level) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Unknown night light level {level}"
+            )));
+        }
+        self.notify(|published, state| published.level = state.level());
+        Ok(())
+    }
+
+    /// Color temperature in Kelvin, clamped to 1000K - 6500K.
+    #[zbus(property, name = "Temperature")]
+    fn temperature(&self) -> u32 {
+        self.state.lock().temperature
     }
 
-    #[zbus(name = "SetLevel")]
-    fn set_level(&mut self, level: u8) {
-        self.state.lock().level = level;
+    #[zbus(property, name = "Temperature")]
+    fn set_temperature(&mut self, kelvin: u32) {
+        self.state.lock().set_temperature(kelvin);
+        self.notify(|published, state| published.temperature = state.temperature);
+    }
+
+    /// Moves the temperature one step warmer or cooler and turns night light on,
+    /// like the keyboard shortcuts. Returns the new temperature.
+    #[zbus(name = "StepTemperature")]
+    fn step_temperature(&mut self, warmer: bool) -> u32 {
+        let kelvin = {
+            let mut state = self.state.lock();
+            state.step_temperature(warmer);
+            state.temperature
+        };
+        // not a property setter, leave `Temperature` and `Enabled` to `publish_changes`
+        let _ = self.changed.send(());
+        kelvin
+    }
+
+    /// Switches to the next intensity preset and turns night light on. Returns the new level.
+    #[zbus(name = "CycleLevel")]
+    fn cycle_level(&mut self) -> u8 {
+        let level = {
+            let mut state = self.state.lock();
+            state.cycle_level();
+            state.level()
+        };
+        let _ = self.changed.send(());
+        level
+    }
+
+    /// Duration of the fade between tints in milliseconds, `0` disables the animation.
+    #[zbus(property, name = "TransitionDuration")]
+    fn transition_duration(&self) -> u32 {
+        self.state.lock().transition_duration.as_millis() as u32
+    }
+
+    #[zbus(property, name = "TransitionDuration")]
+    fn set_transition_duration(&mut self, millis: u32) {
+        self.state
+            .lock()
+            .set_transition_duration(Duration::from_millis(millis as u64));
+        self.notify(|published, state| published.transition_duration = state.transition_duration);
+    }
+
+    /// One of `disabled`, `custom` or `solar`.
+    #[zbus(property, name = "ScheduleMode")]
+    fn schedule_mode(&self) -> String {
+        self.state.lock().schedule_mode.as_str().to_string()
+    }
+
+    #[zbus(property, name = "ScheduleMode")]
+    fn set_schedule_mode(&mut self, mode: String) -> fdo::Result<()> {
+        let mode = mode.parse::<ScheduleMode>().map_err(|_| {
+            fdo::Error::InvalidArgs(format!("Unknown night light schedule mode {mode}"))
+        })?;
+        self.state.lock().set_schedule_mode(mode);
+        self.notify(|published, state| published.schedule_mode = state.schedule_mode);
+        Ok(())
+    }
+
+    /// Start of the custom schedule, in minutes since midnight.
+    #[zbus(property, name = "ScheduleFrom")]
+    fn schedule_from(&self) -> u16 {
+        self.state.lock().schedule_from
+    }
+
+    #[zbus(property, name = "ScheduleFrom")]
+    fn set_schedule_from(&mut self, minute: u16) -> fdo::Result<()> {
+        if !self.state.lock().set_schedule_from(minute) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Invalid minute of day {minute}"
+            )));
+        }
+        self.notify(|published, state| published.schedule_from = state.schedule_from);
+        Ok(())
+    }
+
+    /// End of the custom schedule, in minutes since midnight.
+    #[zbus(property, name = "ScheduleTo")]
+    fn schedule_to(&self) -> u16 {
+        self.state.lock().schedule_to
+    }
+
+    #[zbus(property, name = "ScheduleTo")]
+    fn set_schedule_to(&mut self, minute: u16) -> fdo::Result<()> {
+        if !self.state.lock().set_schedule_to(minute) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Invalid minute of day {minute}"
+            )));
+        }
+        self.notify(|published, state| published.schedule_to = state.schedule_to);
+        Ok(())
+    }
+
+    /// Minutes night light takes to fade in after the evening transition
+    /// and out after the morning one, `0` switches right away.
+    #[zbus(property, name = "ScheduleRamp")]
+    fn schedule_ramp(&self) -> u16 {
+        self.state.lock().schedule_ramp
+    }
+
+    #[zbus(property, name = "ScheduleRamp")]
+    fn set_schedule_ramp(&mut self, minutes: u16) -> fdo::Result<()> {
+        if !self.state.lock().set_schedule_ramp(minutes) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Ramp of {minutes} minutes is longer than {MAX_SCHEDULE_RAMP}"
+            )));
+        }
+        self.notify(|published, state| published.schedule_ramp = state.schedule_ramp);
+        Ok(())
+    }
+
+    /// Latitude and longitude in degrees used by the solar schedule, north and east are positive.
+    #[zbus(property, name = "Location")]
+    fn location(&self) -> fdo::Result<(f64, f64)> {
+        self.state
+            .lock()
+            .location
+            .ok_or_else(|| fdo::Error::Failed("No location configured".into()))
+    }
+
+    #[zbus(property, name = "Location")]
+    fn set_location(&mut self, location: (f64, f64)) -> fdo::Result<()> {
+        let (latitude, longitude) = location;
+        if !self.state.lock().set_location(latitude, longitude) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Invalid location {latitude}, {longitude}"
+            )));
+        }
+        self.notify(|published, state| published.location = state.location);
+        Ok(())
+    }
+
+    /// Capture sources that include the tint, out of `output`, `workspace`, `toplevel`
+    /// and `screenshot`. Captures of anything else stay untinted.
+    #[zbus(property, name = "TintCaptures")]
+    fn tint_captures(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .tint_captures
+            .iter()
+            .map(|source| source.as_str().to_string())
+            .collect()
+    }
+
+    #[zbus(property, name = "TintCaptures")]
+    fn set_tint_captures(&mut self, sources: Vec<String>) -> fdo::Result<()> {
+        let sources = sources
+            .iter()
+            .map(|source| {
+                source.parse::<CaptureSource>().map_err(|_| {
+                    fdo::Error::InvalidArgs(format!("Unknown capture source {source}"))
+                })
+            })
+            .collect::<fdo::Result<Vec<_>>>()?;
+        self.state.lock().set_tint_captures(sources);
+        self.notify(|published, state| published.tint_captures = state.tint_captures.clone());
+        Ok(())
+    }
+
+    /// Brighten dark colors back up, so the tint doesn't swallow shadows.
+    #[zbus(property, name = "PreserveLuminance")]
+    fn preserve_luminance(&self) -> bool {
+        self.state.lock().preserve_luminance
+    }
+
+    #[zbus(property, name = "PreserveLuminance")]
+    fn set_preserve_luminance(&mut self, preserve_luminance: bool) {
+        self.state.lock().set_preserve_luminance(preserve_luminance);
+        self.notify(|published, state| published.preserve_luminance = state.preserve_luminance);
+    }
+
+    /// Share of the light taken away while night light is active, from `0.0` up to `0.8`.
+    ///
+    /// Follows the schedule and the per output settings just like the temperature.
+    #[zbus(property, name = "Dim")]
+    fn dim(&self) -> f64 {
+        self.state.lock().dim as f64
+    }
+
+    #[zbus(property, name = "Dim")]
+    fn set_dim(&mut self, dim: f64) -> fdo::Result<()> {
+        if !self.state.lock().set_dim(dim as f32) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Dim {dim} is out of range 0.0 to {MAX_DIM}"
+            )));
+        }
+        self.notify(|published, state| published.dim = state.dim);
+        Ok(())
+    }
+
+    /// Fullscreen content that pauses night light on its output, out of `video` and `game`,
+    /// as declared by the window through `wp_content_type_v1`. Empty by default.
+    #[zbus(property, name = "PauseForContent")]
+    fn pause_for_content(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .pause_for
+            .iter()
+            .map(|kind| kind.as_str().to_string())
+            .collect()
+    }
+
+    #[zbus(property, name = "PauseForContent")]
+    fn set_pause_for_content(&mut self, kinds: Vec<String>) -> fdo::Result<()> {
+        let kinds = kinds
+            .iter()
+            .map(|kind| {
+                kind.parse::<ContentKind>()
+                    .map_err(|_| fdo::Error::InvalidArgs(format!("Unknown content type {kind}")))
+            })
+            .collect::<fdo::Result<Vec<_>>>()?;
+        self.state.lock().set_pause_for(kinds);
+        self.notify(|published, state| published.pause_for = state.pause_for.clone());
+        Ok(())
+    }
+
+    /// Connectors of the outputs night light is paused on right now,
+    /// with the fullscreen content that paused it, `video` or `game`.
+    #[zbus(property, name = "SuspendReasons")]
+    fn suspend_reasons(&self) -> HashMap<String, String> {
+        self.state
+            .lock()
+            .suspended()
+            .iter()
+            .map(|(output, kind)| (output.connector.clone(), kind.as_str().to_string()))
+            .collect()
+    }
+
+    /// Whether any client currently keeps night light off through `Inhibit`.
+    #[zbus(property, name = "Inhibited")]
+    fn inhibited(&self) -> bool {
+        self.state.lock().is_inhibited()
+    }
+
+    /// Turns night light off on all outputs, without changing the user's settings,
+    /// until `Uninhibit` is called with the returned cookie or the caller leaves the bus.
+    #[zbus(name = "Inhibit")]
+    fn inhibit(&mut self, #[zbus(header)] header: Header<'_>, reason: String) -> fdo::Result<u32> {
+        let sender = header
+            .sender()
+            .ok_or_else(|| fdo::Error::Failed("Inhibit requires a sender".into()))?;
+        let cookie = self.state.lock().inhibit(sender.to_string(), reason);
+        // not a property setter, leave `Inhibited` to `publish_changes`
+        let _ = self.changed.send(());
+        Ok(cookie)
+    }
+
+    /// Ends an inhibit of the caller, night light comes back once no inhibits are left.
+    #[zbus(name = "Uninhibit")]
+    fn uninhibit(&mut self, #[zbus(header)] header: Header<'_>, cookie: u32) -> fdo::Result<()> {
+        let sender = header
+            .sender()
+            .ok_or_else(|| fdo::Error::Failed("Uninhibit requires a sender".into()))?;
+        if !self.state.lock().uninhibit(cookie, sender.as_str()) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Unknown inhibit cookie {cookie}"
+            )));
+        }
+        let _ = self.changed.send(());
+        Ok(())
+    }
+
+    /// What the schedule asks for right now, `enabled`, `disabled` or empty without a schedule.
+    /// Night light differs from it while it was changed by hand or overridden.
+    #[zbus(property, name = "Scheduled")]
+    fn scheduled(&self) -> String {
+        match self.state.lock().scheduled() {
+            Some(true) => "enabled".into(),
+            Some(false) => "disabled".into(),
+            None => String::new(),
+        }
+    }
+
+    /// The active timed override, `enabled`, `disabled` or empty if the schedule is in control.
+    #[zbus(property, name = "Override")]
+    fn override_(&self) -> String {
+        match self.state.lock().timed_override() {
+            Some(o) if o.enabled => "enabled".into(),
+            Some(_) => "disabled".into(),
+            None => String::new(),
+        }
+    }
+
+    /// When the active override ends as unix timestamp,
+    /// `0` if it lasts until the next schedule transition or there is none.
+    #[zbus(property, name = "OverrideExpiry")]
+    fn override_expiry(&self) -> i64 {
+        match self.state.lock().timed_override().map(|o| o.expiry) {
+            Some(OverrideExpiry::At(at)) => at.unix_timestamp(),
+            Some(OverrideExpiry::NextTransition) | None => 0,
+        }
+    }
+
+    /// Turns night light off until the unix timestamp `until`, then the schedule takes over again.
+    #[zbus(name = "DisableUntil")]
+    fn disable_until(&mut self, until: i64) -> fdo::Result<()> {
+        let until = OffsetDateTime::from_unix_timestamp(until)
+            .ok()
+            .filter(|until| *until > OffsetDateTime::now_utc())
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("{until} is not in the future")))?;
+        self.set_override(false, OverrideExpiry::At(until));
+        Ok(())
+    }
+
+    /// Turns night light on for `seconds`, then the schedule takes over again.
+    #[zbus(name = "EnableFor")]
+    fn enable_for(&mut self, seconds: u32) -> fdo::Result<()> {
+        if seconds == 0 {
+            return Err(fdo::Error::InvalidArgs(
+                "Override duration must not be zero".into(),
+            ));
+        }
+        let until = OffsetDateTime::now_utc() + Duration::from_secs(seconds as u64);
+        self.set_override(true, OverrideExpiry::At(until));
+        Ok(())
+    }
+
+    /// Turns night light off until the schedule would turn it on or off next.
+    #[zbus(name = "DisableUntilNextTransition")]
+    fn disable_until_next_transition(&mut self) -> fdo::Result<()> {
+        if !self.state.lock().has_schedule() {
+            return Err(fdo::Error::Failed(
+                "No night light schedule is active".into(),
+            ));
+        }
+        self.set_override(false, OverrideExpiry::NextTransition);
+        Ok(())
+    }
+
+    /// Connected outputs as connector, make and model, enabled, temperature
+    /// and whether any of those are overridden for the output.
+    #[zbus(name = "GetOutputs")]
+    fn get_outputs(&self) -> Vec<(String, String, bool, u32, bool)> {
+        let outputs = self.outputs();
+        let state = self.state.lock();
+        outputs
+            .iter()
+            .map(|output| {
+                let key = OutputKey::from(output);
+                let physical = output.physical_properties();
+                (
+                    key.connector.clone(),
+                    format!("{} {}", physical.make, physical.model),
+                    state.output_enabled(&key),
+                    state.output_temperature(&key),
+                    state.outputs.contains_key(&key),
+                )
+            })
+            .collect()
+    }
+
+    /// Uses `kelvin` for the given output instead of the global temperature.
+    #[zbus(name = "SetOutputTemperature")]
+    fn set_output_temperature(&mut self, connector: String, kelvin: u32) -> fdo::Result<()> {
+        let output = self.output(&connector)?;
+        self.state
+            .lock()
+            .set_output_temperature(output, Some(kelvin));
+        self.notify(|_, _| {});
+        Ok(())
+    }
+
+    /// Turns night light on or off for the given output, regardless of `Enabled` and the schedule.
+    #[zbus(name = "SetOutputEnabled")]
+    fn set_output_enabled(&mut self, connector: String, enabled: bool) -> fdo::Result<()> {
+        let output = self.output(&connector)?;
+        self.state.lock().set_output_enabled(output, Some(enabled));
+        self.notify(|_, _| {});
+        Ok(())
+    }
+
+    /// Drops all overrides of the given output, so it follows the global settings again.
+    #[zbus(name = "ResetOutput")]
+    fn reset_output(&mut self, connector: String) -> fdo::Result<()> {
+        let output = self.output(&connector)?;
+        {
+            let mut state = self.state.lock();
+            state.set_output_enabled(output.clone(), None);
+            state.set_output_temperature(output, None);
+        }
+        self.notify(|_, _| {});
+        Ok(())
     }
 }
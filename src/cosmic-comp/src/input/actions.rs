@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use crate::{
-    config::{Action, PrivateAction},
+    config::{Action, NightLightAction, PrivateAction},
     shell::{
         FocusResult, InvalidWorkspaceIndex, MoveResult, SeatExt, Trigger, WorkspaceDelta,
         focus::{FocusTarget, target::KeyboardFocusTarget},
@@ -57,6 +57,17 @@ impl State {
             return;
         }
 
+        // shortcuts calling the installed `night-light-ctl` are handled right here
+        let action = match action {
+            Action::Shortcut(shortcuts::Action::Spawn(command)) => {
+                match NightLightAction::from_command(&command) {
+                    Some(action) => Action::Private(PrivateAction::NightLight(action)),
+                    None => Action::Shortcut(shortcuts::Action::Spawn(command)),
+                }
+            }
+            action => action,
+        };
+
         match action {
             Action::Shortcut(action) => {
                 let propagate = propagate_by_default(&action);
@@ -97,6 +108,22 @@ impl State {
                         .finish_resize(direction, edge.into());
                 }
             }
+
+            Action::Private(PrivateAction::NightLight(action)) => {
+                {
+                    let mut night_light = self.common.night_light.lock();
+                    match action {
+                        NightLightAction::Toggle => {
+                            let enabled = !night_light.enabled;
+                            night_light.set_enabled_by_hand(enabled);
+                        }
+                        NightLightAction::Warmer => night_light.step_temperature(true),
+                        NightLightAction::Cooler => night_light.step_temperature(false),
+                        NightLightAction::CyclePresets => night_light.cycle_level(),
+                    }
+                }
+                self.update_night_light();
+            }
         }
     }
 
@@ -36,6 +36,7 @@ pub mod debug;
 pub mod hooks;
 pub mod input;
 mod logger;
+pub mod night_light;
 pub mod session;
 pub mod shell;
 pub mod state;
@@ -61,7 +62,7 @@ impl State {
             if let state::BackendData::Kms(_) = &self.backend {
                 #[cfg(feature = "systemd")]
                 systemd::ready(&self.common);
-                if let Err(err) = dbus::ready(&self.common) {
+                if let Err(err) = dbus::ready(&mut self.common) {
                     error!(?err, "Failed to update the D-Bus activation environment");
                 }
             }
@@ -194,7 +195,7 @@ pub fn run(hooks: crate::hooks::Hooks) -> Result<(), Box<dyn Error>> {
 
         {
             let shell = state.common.shell.read();
-            if shell.animations_going() {
+            if shell.animations_going() || state.common.night_light.lock().is_animating() {
                 for output in shell.outputs().cloned().collect::<Vec<_>>().into_iter() {
                     state.backend.schedule_render(&output);
                 }
@@ -322,6 +323,7 @@ fn refresh(state: &mut State) {
 
     state.common.refresh();
     state::Common::refresh_focus(state);
+    state.refresh_night_light_suspension();
     OverlapNotifyState::refresh(state);
     state.common.update_x11_stacking_order();
     state.last_refresh = LastRefresh::At(Instant::now());
@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use smithay::utils::{Logical, Physical, Rectangle};
+
+use super::Adaptation;
+
+/// Where a screenshot or screencast comes from, to decide if it shows the night light tint.
+///
+/// Captures are rendered without the postprocess pass, so they are untinted unless
+/// their source is listed in `TintCaptures`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureSource {
+    /// Whole outputs, e.g. screen sharing, "what I see"
+    Output,
+    /// A single workspace, e.g. the workspace overview
+    Workspace,
+    /// A single window through image-copy-capture
+    Toplevel,
+    /// The window menu's screenshot entry
+    Screenshot,
+}
+
+impl CaptureSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaptureSource::Output => "output",
+            CaptureSource::Workspace => "workspace",
+            CaptureSource::Toplevel => "toplevel",
+            CaptureSource::Screenshot => "screenshot",
+        }
+    }
+}
+
+impl FromStr for CaptureSource {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "output" => Ok(CaptureSource::Output),
+            "workspace" => Ok(CaptureSource::Workspace),
+            "toplevel" => Ok(CaptureSource::Toplevel),
+            "screenshot" => Ok(CaptureSource::Screenshot),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The night light tint of a capture, as returned by `NightLightState::capture_adaptation`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureTint {
+    pub adaptation: Adaptation,
+    /// Areas of windows exempt from night light, left untinted just like on screen
+    pub exempt: Vec<Rectangle<i32, Physical>>,
+}
+
+impl CaptureTint {
+    /// Takes `exempt` as returned by `Shell::night_light_exempt_regions`, for a capture at `scale`.
+    pub fn new(adaptation: Adaptation, exempt: &[Rectangle<i32, Logical>], scale: f64) -> Self {
+        CaptureTint {
+            adaptation,
+            exempt: exempt
+                .iter()
+                .map(|rect| rect.to_physical_precise_up(scale))
+                .collect(),
+        }
+    }
+}
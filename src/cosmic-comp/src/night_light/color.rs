@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use super::NEUTRAL_TEMPERATURE;
+
+/// Approximates the sRGB color of a blackbody radiator at the given temperature.
+///
+/// Based on Tanner Helland's curve fit of Mitchell Charity's blackbody tables,
+/// which is accurate enough for the 1000K - 40000K range. Channels are in `0.0..=1.0`.
+fn blackbody(kelvin: u32) -> [f64; 3] {
+    let temp = kelvin as f64 / 100.;
+
+    let red = if temp <= 66. {
+        255.
+    } else {
+        329.698727446 * (temp - 60.).powf(-0.1332047592)
+    };
+    let green = if temp <= 66. {
+        99.4708025861 * temp.ln() - 161.1195681661
+    } else {
+        288.1221695283 * (temp - 60.).powf(-0.0755148492)
+    };
+    let blue = if temp >= 66. {
+        255.
+    } else if temp <= 19. {
+        0.
+    } else {
+        138.5177312231 * (temp - 10.).ln() - 305.0447927307
+    };
+
+    [red, green, blue].map(|channel| (channel / 255.).clamp(0., 1.))
+}
+
+/// Per-channel gains to shift the output white point to `kelvin`.
+///
+/// Gains are relative to [`NEUTRAL_TEMPERATURE`], so passing that value returns `[1.0; 3]`.
+pub fn temperature_to_rgb(kelvin: u32) -> [f32; 3] {
+    let target = blackbody(kelvin);
+    let neutral = blackbody(NEUTRAL_TEMPERATURE);
+
+    [0, 1, 2].map(|i| (target[i] / neutral[i]).clamp(0., 1.) as f32)
+}
+
+/// Rec. 709 luma coefficients, for linear sRGB values.
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Upper bound for the brightness compensation of `preserve_luminance`.
+///
+/// Keeps the adaptation monotonic, so a brighter input never ends up darker.
+const MAX_COMPENSATION: f32 = 3.;
+
+/// Decodes an sRGB encoded channel in `0.0..=1.0` to linear light.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear light channel in `0.0..=1.0` to sRGB.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+fn luminance(rgb: [f32; 3]) -> f32 {
+    rgb[0] * LUMA[0] + rgb[1] * LUMA[1] + rgb[2] * LUMA[2]
+}
+
+/// How the night light changes a color.
+///
+/// This is the reference for `offscreen.frag`, which has to do exactly the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adaptation {
+    /// sRGB encoded color white turns into, as returned by [`temperature_to_rgb`]
+    pub gains: [f32; 3],
+    /// Compensate the brightness the tint takes away, fully for black and fading out
+    /// towards white, so darks don't sink while white still hits the target white point
+    pub preserve_luminance: bool,
+    /// Linear light multiplier applied last, so the extra dim doesn't get compensated
+    pub brightness: f32,
+}
+
+impl Default for Adaptation {
+    fn default() -> Self {
+        Adaptation::NEUTRAL
+    }
+}
+
+impl Adaptation {
+    pub const NEUTRAL: Adaptation = Adaptation {
+        gains: [1.0; 3],
+        preserve_luminance: false,
+        brightness: 1.0,
+    };
+
+    pub fn is_neutral(&self) -> bool {
+        self.gains == [1.0; 3] && self.brightness == 1.0
+    }
+
+    /// Adapts a straight alpha, sRGB encoded color with channels in `0.0..=1.0`.
+    ///
+    /// The white point gains are applied in linear light, scaling the encoded values instead
+    /// would crush dark colors and shift bright ones unevenly.
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        if self.is_neutral() {
+            return rgb;
+        }
+
+        let linear = rgb.map(|value| srgb_to_linear(value.clamp(0., 1.)));
+        let gains = self.gains.map(srgb_to_linear);
+        let mut adapted = [0, 1, 2].map(|i| linear[i] * gains[i]);
+
+        if self.preserve_luminance {
+            let compensation = (1. / luminance(gains).max(f32::EPSILON) - 1.).min(MAX_COMPENSATION);
+            let darkness = 1. - luminance(linear).clamp(0., 1.);
+            let boost = 1. + compensation * darkness * darkness;
+            adapted = adapted.map(|value| value * boost);
+
+            // scale saturated colors back into range instead of clipping, which shifts the hue
+            let peak = adapted.into_iter().fold(0., f32::max);
+            if peak > 1. {
+                adapted = adapted.map(|value| value / peak);
+            }
+        }
+
+        adapted.map(|value| linear_to_srgb(value * self.brightness))
+    }
+
+    /// Adapts a single sRGB encoded channel, for per channel lookup tables like the gamma ramp.
+    ///
+    /// Matches [`Adaptation::apply`] only without `preserve_luminance`, which mixes channels.
+    pub fn apply_channel(&self, channel: usize, value: f32) -> f32 {
+        if self.gains[channel] == 1. && self.brightness == 1. {
+            return value;
+        }
+        linear_to_srgb(
+            srgb_to_linear(value.clamp(0., 1.))
+                * srgb_to_linear(self.gains[channel])
+                * self.brightness,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Adaptation, linear_to_srgb, srgb_to_linear, temperature_to_rgb};
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!(
+            (0..3).all(|i| (a[i] - b[i]).abs() < 1e-4),
+            "{a:?} is not close to {b:?}"
+        );
+    }
+
+    #[test]
+    fn test_srgb_roundtrip() {
+        for i in 0..=255 {
+            let value = i as f32 / 255.;
+            assert!((linear_to_srgb(srgb_to_linear(value)) - value).abs() < 1e-5);
+        }
+        assert_eq!(srgb_to_linear(0.), 0.);
+        assert!((srgb_to_linear(1.) - 1.).abs() < 1e-6);
+        // mid grey is about 21% of the light
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_neutral() {
+        let color = [0.2, 0.5, 0.9];
+        assert_eq!(Adaptation::NEUTRAL.apply(color), color);
+        let neutral = Adaptation {
+            gains: temperature_to_rgb(6500),
+            preserve_luminance: true,
+            brightness: 1.,
+        };
+        assert_eq!(neutral.apply(color), color);
+    }
+
+    #[test]
+    fn test_white_hits_white_point() {
+        for kelvin in [1000, 2900, 3800, 4800] {
+            let gains = temperature_to_rgb(kelvin);
+            for preserve_luminance in [false, true] {
+                let adaptation = Adaptation {
+                    gains,
+                    preserve_luminance,
+                    brightness: 1.,
+                };
+                assert_close(adaptation.apply([1.; 3]), gains);
+            }
+        }
+    }
+
+    #[test]
+    fn test_linear_light() {
+        let adaptation = Adaptation {
+            gains: temperature_to_rgb(2900),
+            preserve_luminance: false,
+            brightness: 1.,
+        };
+        // the gains scale the light, so the ratio between two greys stays the same in every channel
+        let dark = adaptation.apply([0.1; 3]).map(srgb_to_linear);
+        let bright = adaptation.apply([0.8; 3]).map(srgb_to_linear);
+        let ratio = srgb_to_linear(0.1) / srgb_to_linear(0.8);
+        for i in 0..3 {
+            assert!((dark[i] / bright[i] - ratio).abs() < 1e-3);
+        }
+        // black stays black, each channel is independent
+        assert_eq!(adaptation.apply([0.; 3]), [0.; 3]);
+        let color = [0.3, 0.6, 0.8];
+        let adapted = adaptation.apply(color);
+        for i in 0..3 {
+            assert!((adaptation.apply_channel(i, color[i]) - adapted[i]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_preserve_luminance() {
+        let gains = temperature_to_rgb(2900);
+        let plain = Adaptation {
+            gains,
+            preserve_luminance: false,
+            brightness: 1.,
+        };
+        let preserving = Adaptation {
+            gains,
+            preserve_luminance: true,
+            brightness: 1.,
+        };
+
+        // darks are brightened back up, but keep their tint
+        let grey = [0.2; 3];
+        let dark = preserving.apply(grey);
+        assert!(dark[0] > plain.apply(grey)[0]);
+        assert!(dark[0] > dark[1] && dark[1] > dark[2]);
+
+        // and brighter inputs never turn out darker
+        let mut previous = [0.; 3];
+        for i in 0..=255 {
+            let adapted = Adaptation {
+                gains: temperature_to_rgb(1000),
+                preserve_luminance: true,
+                brightness: 1.,
+            }
+            .apply([i as f32 / 255.; 3]);
+            assert!((0..3).all(|c| adapted[c] + 1e-6 >= previous[c]));
+            assert!(adapted.iter().all(|value| (0. ..=1.).contains(value)));
+            previous = adapted;
+        }
+    }
+
+    #[test]
+    fn test_brightness() {
+        let gains = temperature_to_rgb(3800);
+        let tinted = Adaptation {
+            gains,
+            preserve_luminance: false,
+            brightness: 1.,
+        };
+        for preserve_luminance in [false, true] {
+            let dimmed = Adaptation {
+                gains,
+                preserve_luminance,
+                brightness: 0.5,
+            };
+            assert!(!dimmed.is_neutral());
+            // the dim halves the light and isn't undone by the compensation
+            assert_close(
+                dimmed.apply([1.; 3]).map(srgb_to_linear),
+                tinted
+                    .apply([1.; 3])
+                    .map(|value| srgb_to_linear(value) * 0.5),
+            );
+        }
+
+        let color = [0.3, 0.6, 0.8];
+        let dimmed = Adaptation {
+            brightness: 0.5,
+            ..Adaptation::NEUTRAL
+        };
+        let adapted = dimmed.apply(color);
+        for i in 0..3 {
+            assert!((dimmed.apply_channel(i, color[i]) - adapted[i]).abs() < 1e-6);
+        }
+    }
+}
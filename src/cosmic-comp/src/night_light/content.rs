@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use smithay::{
+    reexports::{
+        wayland_protocols::wp::content_type::v1::server::wp_content_type_v1,
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
+    wayland::{
+        compositor::with_states, content_type::ContentTypeSurfaceCachedState, seat::WaylandFocus,
+    },
+};
+
+use super::OutputKey;
+use crate::state::State;
+
+/// Content declared through `wp_content_type_v1`, that night light can pause for.
+///
+/// Pausing is opt-in, nothing is paused unless listed in `PauseForContent`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    Video,
+    Game,
+}
+
+impl ContentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentKind::Video => "video",
+            ContentKind::Game => "game",
+        }
+    }
+
+    /// What `surface` declared it shows, `None` for anything but video and games.
+    pub fn of_surface(surface: &WlSurface) -> Option<ContentKind> {
+        with_states(surface, |states| {
+            match states
+                .cached_state
+                .get::<ContentTypeSurfaceCachedState>()
+                .current()
+                .content_type()
+            {
+                wp_content_type_v1::Type::Video => Some(ContentKind::Video),
+                wp_content_type_v1::Type::Game => Some(ContentKind::Game),
+                _ => None,
+            }
+        })
+    }
+}
+
+impl FromStr for ContentKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "video" => Ok(ContentKind::Video),
+            "game" => Ok(ContentKind::Game),
+            _ => Err(()),
+        }
+    }
+}
+
+impl State {
+    /// Pauses night light on outputs with fullscreen content listed in `PauseForContent`,
+    /// and brings it back once that window leaves fullscreen.
+    pub fn refresh_night_light_suspension(&mut self) {
+        let suspended = {
+            let shell = self.common.shell.read();
+            let pause_for = self.common.night_light.lock().pause_for.clone();
+            if pause_for.is_empty() {
+                BTreeMap::new()
+            } else {
+                shell
+                    .outputs()
+                    .filter_map(|output| {
+                        let (_, workspace) = shell.workspaces.active(output)?;
+                        let surface = workspace.get_fullscreen()?.wl_surface()?;
+                        let kind = ContentKind::of_surface(&surface)?;
+                        pause_for
+                            .contains(&kind)
+                            .then(|| (OutputKey::from(output), kind))
+                    })
+                    .collect()
+            }
+        };
+
+        if self.common.night_light.lock().set_suspended(suspended) {
+            self.update_night_light();
+        }
+    }
+}
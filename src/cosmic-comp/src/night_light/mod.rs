@@ -0,0 +1,860 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    time::{Duration, Instant},
+};
+
+use keyframe::{ease, functions::EaseInOutCubic};
+use smithay::output::Output;
+use time::{Date, OffsetDateTime};
+
+use crate::{
+    config::{EdidProduct, NightLightConfig, NightLightOverrideConfig, OutputNightLightConfig},
+    state::State,
+    utils::prelude::OutputExt,
+};
+
+pub mod capture;
+mod color;
+pub mod content;
+pub mod schedule;
+pub mod solar;
+
+pub use self::color::{Adaptation, temperature_to_rgb};
+use self::{
+    capture::CaptureSource,
+    content::ContentKind,
+    schedule::{MAX_SCHEDULE_RAMP, MINUTES_PER_DAY, ScheduleMode},
+    solar::Twilight,
+};
+
+/// Color temperature (in Kelvin) that leaves the screen untouched.
+pub const NEUTRAL_TEMPERATURE: u32 = 6500;
+/// Warmest color temperature we allow.
+pub const MIN_TEMPERATURE: u32 = 1000;
+
+/// Temperatures for the intensity levels 1 to 3 (soft, warm, strong).
+///
+/// These roughly match the fixed tints we used before supporting arbitrary temperatures.
+pub const LEVEL_PRESETS: [u32; 3] = [4800, 3800, 2900];
+
+pub const DEFAULT_TRANSITION_DURATION: Duration = Duration::from_millis(1000);
+/// Upper bound for the fade duration, so a typo can't leave the screen animating for hours.
+pub const MAX_TRANSITION_DURATION: Duration = Duration::from_secs(60);
+/// Most light the extra dim may take away, so the screen can't go black.
+pub const MAX_DIM: f32 = 0.8;
+/// How far the warmer and cooler shortcuts move the temperature.
+pub const TEMPERATURE_STEP: u32 = 250;
+
+/// Identifies a monitor across reconnects, the connector alone doesn't tell two monitors apart.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OutputKey {
+    pub connector: String,
+    pub edid: Option<EdidProduct>,
+}
+
+impl From<&Output> for OutputKey {
+    fn from(output: &Output) -> Self {
+        OutputKey {
+            connector: output.name(),
+            edid: output.edid().cloned(),
+        }
+    }
+}
+
+/// Settings of a single output, that replace the global ones.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OutputOverride {
+    pub enabled: Option<bool>,
+    pub temperature: Option<u32>,
+}
+
+impl OutputOverride {
+    fn is_empty(&self) -> bool {
+        self.enabled.is_none() && self.temperature.is_none()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Transition {
+    start: Instant,
+    /// Adaptation of outputs without overrides when the fade started
+    from: Adaptation,
+    /// Adaptation of outputs with overrides when the fade started
+    from_outputs: HashMap<OutputKey, Adaptation>,
+}
+
+/// A client keeping night light off, e.g. a display calibration tool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inhibitor {
+    /// Unique D-Bus name of the client, so the inhibit ends when it exits
+    pub owner: String,
+    pub reason: String,
+}
+
+/// When a [`TimedOverride`] hands control back to the schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrideExpiry {
+    At(OffsetDateTime),
+    /// The next time the schedule flips
+    NextTransition,
+}
+
+/// Night light forced on or off for a while, e.g. "disable until tomorrow".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOverride {
+    pub enabled: bool,
+    pub expiry: OverrideExpiry,
+    /// `enabled` before the override, restored on expiry if there is no schedule to follow
+    previous: bool,
+}
+
+impl TimedOverride {
+    /// `None` if the stored expiry is out of range.
+    fn from_config(config: &NightLightOverrideConfig) -> Option<Self> {
+        let expiry = match config.until {
+            Some(until) => OverrideExpiry::At(OffsetDateTime::from_unix_timestamp(until).ok()?),
+            None => OverrideExpiry::NextTransition,
+        };
+        Some(TimedOverride {
+            enabled: config.enabled,
+            expiry,
+            previous: config.previous,
+        })
+    }
+
+    fn config(&self) -> NightLightOverrideConfig {
+        NightLightOverrideConfig {
+            enabled: self.enabled,
+            until: match self.expiry {
+                OverrideExpiry::At(at) => Some(at.unix_timestamp()),
+                OverrideExpiry::NextTransition => None,
+            },
+            previous: self.previous,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NightLightState {
+    pub enabled: bool,
+    pub temperature: u32,
+    pub transition_duration: Duration,
+    transition: Option<Transition>,
+    /// Per output settings, falling back to `enabled` and `temperature`
+    pub outputs: BTreeMap<OutputKey, OutputOverride>,
+    pub schedule_mode: ScheduleMode,
+    /// Start of the custom schedule, in minutes since midnight
+    pub schedule_from: u16,
+    /// End of the custom schedule, in minutes since midnight
+    pub schedule_to: u16,
+    /// Minutes night light takes to fade in or out after each transition of the schedule
+    pub schedule_ramp: u16,
+    /// Latitude and longitude in degrees, for the solar schedule
+    pub location: Option<(f64, f64)>,
+    /// Twilight times of the local day we computed them for
+    twilight: Option<(Date, Twilight)>,
+    /// What the schedule asked for last time, so manual changes stick until the next transition
+    scheduled: Option<bool>,
+    /// When the schedule was last evaluated, persisted along with manual changes
+    evaluated_at: Option<OffsetDateTime>,
+    /// `evaluated_at` as restored from the config, until the first evaluation after a restart
+    /// checks it for transitions missed while we weren't running
+    restored_at: Option<OffsetDateTime>,
+    /// How far the schedule ramp faded the tint in, from `0.0` to `1.0`
+    ramp: f32,
+    /// Capture sources that include the tint, everything else is captured untinted
+    pub tint_captures: BTreeSet<CaptureSource>,
+    pub preserve_luminance: bool,
+    /// Share of the light taken away (in linear light) while night light is active
+    pub dim: f32,
+    /// Fullscreen content that pauses night light on its output
+    pub pause_for: BTreeSet<ContentKind>,
+    /// Outputs paused right now, and the content they show
+    suspended: BTreeMap<OutputKey, ContentKind>,
+    /// Outputs whose gamma ramp a gamma-control client took over, replacing the night light
+    gamma_clients: BTreeSet<OutputKey>,
+    /// Night light is off everywhere while any client inhibits it, by cookie
+    inhibitors: BTreeMap<u32, Inhibitor>,
+    last_cookie: u32,
+    timed_override: Option<TimedOverride>,
+}
+
+impl NightLightState {
+    pub fn new(config: &NightLightConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            temperature: config
+                .temperature
+                .clamp(MIN_TEMPERATURE, NEUTRAL_TEMPERATURE),
+            transition_duration: Duration::from_millis(config.transition_duration_ms as u64)
+                .min(MAX_TRANSITION_DURATION),
+            // apply the restored tint right away instead of fading in
+            transition: None,
+            outputs: config
+                .outputs
+                .iter()
+                .map(|output| {
+                    (
+                        OutputKey {
+                            connector: output.connector.clone(),
+                            edid: output.edid,
+                        },
+                        OutputOverride {
+                            enabled: output.enabled,
+                            temperature: output
+                                .temperature
+                                .map(|kelvin| kelvin.clamp(MIN_TEMPERATURE, NEUTRAL_TEMPERATURE)),
+                        },
+                    )
+                })
+                .filter(|(_, output)| !output.is_empty())
+                .collect(),
+            schedule_mode: config.schedule_mode,
+            schedule_from: config.schedule_from % MINUTES_PER_DAY,
+            schedule_to: config.schedule_to % MINUTES_PER_DAY,
+            schedule_ramp: config.schedule_ramp.min(MAX_SCHEDULE_RAMP),
+            location: config
+                .location
+                .filter(|(lat, lon)| valid_location(*lat, *lon)),
+            twilight: None,
+            // keeps a manual change until the schedule flips, even across a restart
+            scheduled: config.scheduled,
+            evaluated_at: None,
+            restored_at: config
+                .evaluated_at
+                .and_then(|at| OffsetDateTime::from_unix_timestamp(at).ok()),
+            ramp: 1.,
+            tint_captures: config.tint_captures.iter().copied().collect(),
+            preserve_luminance: config.preserve_luminance,
+            dim: if config.dim.is_nan() {
+                0.
+            } else {
+                config.dim.clamp(0., MAX_DIM)
+            },
+            pause_for: config.pause_for_content.iter().copied().collect(),
+            suspended: BTreeMap::new(),
+            gamma_clients: BTreeSet::new(),
+            inhibitors: BTreeMap::new(),
+            last_cookie: 0,
+            timed_override: config
+                .timed_override
+                .as_ref()
+                .and_then(TimedOverride::from_config),
+        }
+    }
+
+    pub fn config(&self) -> NightLightConfig {
+        NightLightConfig {
+            enabled: self.enabled,
+            temperature: self.temperature,
+            transition_duration_ms: self.transition_duration.as_millis() as u32,
+            schedule_mode: self.schedule_mode,
+            schedule_from: self.schedule_from,
+            schedule_to: self.schedule_to,
+            schedule_ramp: self.schedule_ramp,
+            location: self.location,
+            outputs: self
+                .outputs
+                .iter()
+                .map(|(key, output)| OutputNightLightConfig {
+                    connector: key.connector.clone(),
+                    edid: key.edid,
+                    enabled: output.enabled,
+                    temperature: output.temperature,
+                })
+                .collect(),
+            tint_captures: self.tint_captures.iter().copied().collect(),
+            preserve_luminance: self.preserve_luminance,
+            pause_for_content: self.pause_for.iter().copied().collect(),
+            dim: self.dim,
+            scheduled: self.scheduled,
+            timed_override: self.timed_override.as_ref().map(TimedOverride::config),
+            // only needed to expire manual changes, and would rewrite the config on every update
+            evaluated_at: self
+                .evaluated_at
+                .or(self.restored_at)
+                .filter(|_| self.has_manual_state())
+                .map(OffsetDateTime::unix_timestamp),
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            self.start_transition();
+            self.enabled = enabled;
+        }
+    }
+
+    /// Switches night light from a user action, which ends any timed override.
+    pub fn set_enabled_by_hand(&mut self, enabled: bool) {
+        self.clear_override();
+        self.set_enabled(enabled);
+    }
+
+    pub fn set_temperature(&mut self, kelvin: u32) {
+        let kelvin = kelvin.clamp(MIN_TEMPERATURE, NEUTRAL_TEMPERATURE);
+        if self.temperature != kelvin {
+            self.start_transition();
+            self.temperature = kelvin;
+        }
+    }
+
+    /// Overrides the temperature of a single output, `None` follows the global temperature again.
+    pub fn set_output_temperature(&mut self, output: OutputKey, kelvin: Option<u32>) {
+        let kelvin = kelvin.map(|kelvin| kelvin.clamp(MIN_TEMPERATURE, NEUTRAL_TEMPERATURE));
+        self.update_output(output, |output| output.temperature = kelvin);
+    }
+
+    /// Overrides whether night light is on for a single output, `None` follows the global state again.
+    pub fn set_output_enabled(&mut self, output: OutputKey, enabled: Option<bool>) {
+        self.update_output(output, |output| output.enabled = enabled);
+    }
+
+    fn update_output(&mut self, key: OutputKey, update: impl FnOnce(&mut OutputOverride)) {
+        let mut output = self.outputs.get(&key).copied().unwrap_or_default();
+        update(&mut output);
+        if self.outputs.get(&key).copied().unwrap_or_default() == output {
+            return;
+        }
+
+        self.start_transition();
+        if output.is_empty() {
+            self.outputs.remove(&key);
+        } else {
+            self.outputs.insert(key, output);
+        }
+    }
+
+    /// Whether night light is on for `output`, taking overrides into account.
+    pub fn output_enabled(&self, output: &OutputKey) -> bool {
+        self.outputs
+            .get(output)
+            .and_then(|output| output.enabled)
+            .unwrap_or(self.enabled)
+    }
+
+    /// Whether `output` is tinted, unlike `output_enabled` this includes pauses for fullscreen content.
+    fn output_active(&self, output: &OutputKey) -> bool {
+        self.output_enabled(output) && !self.is_inhibited() && !self.suspended.contains_key(output)
+    }
+
+    /// Temperature used for `output`, taking overrides into account.
+    pub fn output_temperature(&self, output: &OutputKey) -> u32 {
+        self.outputs
+            .get(output)
+            .and_then(|output| output.temperature)
+            .unwrap_or(self.temperature)
+    }
+
+    pub fn set_transition_duration(&mut self, duration: Duration) {
+        self.transition_duration = duration.min(MAX_TRANSITION_DURATION);
+    }
+
+    /// The preset level matching the current temperature, or `0` for custom temperatures.
+    pub fn level(&self) -> u8 {
+        LEVEL_PRESETS
+            .iter()
+            .position(|temperature| *temperature == self.temperature)
+            .map(|idx| idx as u8 + 1)
+            .unwrap_or(0)
+    }
+
+    /// Moves the temperature to the next multiple of [`TEMPERATURE_STEP`] and turns night light on.
+    pub fn step_temperature(&mut self, warmer: bool) {
+        let kelvin = if warmer {
+            self.temperature.saturating_sub(1) / TEMPERATURE_STEP * TEMPERATURE_STEP
+        } else {
+            (self.temperature / TEMPERATURE_STEP + 1) * TEMPERATURE_STEP
+        };
+        self.set_temperature(kelvin);
+        self.set_enabled_by_hand(true);
+    }
+
+    /// Picks the next intensity preset, wrapping around, and turns night light on.
+    pub fn cycle_level(&mut self) {
+        let level = self.level() as usize % LEVEL_PRESETS.len() + 1;
+        self.set_level(level as u8);
+        self.set_enabled_by_hand(true);
+    }
+
+    /// Returns `false` if `level` doesn't name a preset.
+    pub fn set_level(&mut self, level: u8) -> bool {
+        match LEVEL_PRESETS.get((level as usize).wrapping_sub(1)) {
+            Some(temperature) => {
+                self.set_temperature(*temperature);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_schedule_mode(&mut self, mode: ScheduleMode) {
+        self.schedule_mode = mode;
+        self.reschedule();
+        if !self.has_schedule()
+            && self
+                .timed_override
+                .is_some_and(|o| o.expiry == OverrideExpiry::NextTransition)
+        {
+            self.timed_override = None;
+        }
+    }
+
+    /// Returns `false` if `minute` is not a valid time of day.
+    pub fn set_schedule_from(&mut self, minute: u16) -> bool {
+        if minute >= MINUTES_PER_DAY {
+            return false;
+        }
+        self.schedule_from = minute;
+        self.reschedule();
+        true
+    }
+
+    /// Returns `false` if `minute` is not a valid time of day.
+    pub fn set_schedule_to(&mut self, minute: u16) -> bool {
+        if minute >= MINUTES_PER_DAY {
+            return false;
+        }
+        self.schedule_to = minute;
+        self.reschedule();
+        true
+    }
+
+    /// Returns `false` if the coordinates are out of range.
+    pub fn set_location(&mut self, latitude: f64, longitude: f64) -> bool {
+        if !valid_location(latitude, longitude) {
+            return false;
+        }
+        self.location = Some((latitude, longitude));
+        self.twilight = None;
+        self.reschedule();
+        true
+    }
+
+    /// Twilight times for the local day of `now`, recomputed once per day.
+    fn twilight(&mut self, now: &OffsetDateTime) -> Option<Twilight> {
+        let (latitude, longitude) = self.location?;
+        let date = now.date();
+        match self.twilight {
+            Some((cached, twilight)) if cached == date => Some(twilight),
+            _ => {
+                let twilight = solar::civil_twilight(date, latitude, longitude);
+                self.twilight = Some((date, twilight));
+                Some(twilight)
+            }
+        }
+    }
+
+    /// Follows the schedule whenever it flips, ramps the tint around transitions
+    /// and ends expired overrides, returns `true` if that changed anything.
+    pub fn apply_schedule(&mut self, now: &OffsetDateTime) -> bool {
+        self.evaluated_at = Some(*now);
+        let restored_at = self.restored_at.take();
+        let was_enabled = self.enabled;
+        let was_strength = self.ramp_strength();
+        if self
+            .timed_override
+            .is_some_and(|o| matches!(o.expiry, OverrideExpiry::At(at) if at <= *now))
+        {
+            self.end_override();
+        }
+
+        let phase = match self.schedule_mode {
+            ScheduleMode::Disabled => None,
+            ScheduleMode::Custom => Some(schedule::custom_phase(
+                now,
+                self.schedule_from,
+                self.schedule_to,
+            )),
+            ScheduleMode::Solar => self.twilight(now).map(|twilight| twilight.phase(now)),
+        };
+        let Some((night, since_transition)) = phase else {
+            self.scheduled = None;
+            return self.enabled != was_enabled || self.ramp_strength() != was_strength;
+        };
+        let ramp = schedule::ramp_strength(
+            night,
+            since_transition,
+            Duration::from_secs(self.schedule_ramp as u64 * 60),
+        );
+        // stay on until the morning ramp is done
+        let wanted = night || ramp > 0.;
+        // the schedule flipped while we weren't running, e.g. twice while the computer was off;
+        // live transitions are caught by `scheduled` instead, which also respects the ramp
+        let missed = restored_at
+            .and_then(|at| Duration::try_from(*now - at).ok())
+            .is_some_and(|elapsed| since_transition < elapsed);
+
+        if self.ramp != ramp || self.scheduled != Some(wanted) {
+            // the strength of the tint might change, fade from what is on screen
+            self.start_transition();
+        }
+        self.ramp = ramp;
+
+        if self.scheduled != Some(wanted) || missed {
+            // the first evaluation after a schedule change is no transition
+            let flipped = self.scheduled.is_some() || missed;
+            self.scheduled = Some(wanted);
+
+            match self.timed_override {
+                Some(o) if o.expiry == OverrideExpiry::NextTransition && flipped => {
+                    self.timed_override = None;
+                    self.set_enabled(wanted);
+                }
+                Some(_) => {}
+                None => self.set_enabled(wanted),
+            }
+        }
+
+        self.enabled != was_enabled || self.ramp_strength() != was_strength
+    }
+
+    /// How much of the tint the schedule ramp lets through, `1.0` unless the schedule is in control.
+    fn ramp_strength(&self) -> f32 {
+        if self.timed_override.is_none() && self.scheduled == Some(true) {
+            self.ramp
+        } else {
+            1.
+        }
+    }
+
+    /// Whether the schedule is fading night light in or out right now.
+    pub fn is_ramping(&self) -> bool {
+        let strength = self.ramp_strength();
+        strength > 0. && strength < 1.
+    }
+
+    /// Returns `false` if `minutes` exceeds [`MAX_SCHEDULE_RAMP`].
+    pub fn set_schedule_ramp(&mut self, minutes: u16) -> bool {
+        if minutes > MAX_SCHEDULE_RAMP {
+            return false;
+        }
+        self.schedule_ramp = minutes;
+        true
+    }
+
+    /// Whether the schedule decides on `enabled`, otherwise nothing would end
+    /// an override lasting until the next transition.
+    pub fn has_schedule(&self) -> bool {
+        match self.schedule_mode {
+            ScheduleMode::Disabled => false,
+            ScheduleMode::Custom => true,
+            ScheduleMode::Solar => self.location.is_some(),
+        }
+    }
+
+    /// Whether night light deviates from the schedule because of the user.
+    fn has_manual_state(&self) -> bool {
+        self.timed_override.is_some() || self.scheduled.is_some_and(|wanted| wanted != self.enabled)
+    }
+
+    /// What the schedule asks for right now, `None` without an active schedule.
+    pub fn scheduled(&self) -> Option<bool> {
+        self.scheduled
+    }
+
+    pub fn timed_override(&self) -> Option<&TimedOverride> {
+        self.timed_override.as_ref()
+    }
+
+    /// Forces night light on or off until `expiry`, replacing any previous override.
+    pub fn set_override(&mut self, enabled: bool, expiry: OverrideExpiry) {
+        let previous = self.timed_override.map_or(self.enabled, |o| o.previous);
+        // overrides skip the schedule ramp
+        self.start_transition();
+        self.timed_override = Some(TimedOverride {
+            enabled,
+            expiry,
+            previous,
+        });
+        self.set_enabled(enabled);
+    }
+
+    /// Forgets the override without restoring anything, as the user took over.
+    pub fn clear_override(&mut self) {
+        if self.timed_override.is_some() {
+            self.start_transition();
+            self.timed_override = None;
+        }
+    }
+
+    fn end_override(&mut self) {
+        let Some(o) = self.timed_override else {
+            return;
+        };
+        if self.has_schedule() {
+            // evaluate the schedule from scratch, even if it didn't flip meanwhile
+            self.reschedule();
+            self.timed_override = None;
+        } else {
+            self.clear_override();
+            self.set_enabled(o.previous);
+        }
+    }
+
+    /// Makes the next `apply_schedule` follow the schedule, even if it didn't flip.
+    fn reschedule(&mut self) {
+        // the schedule ramp doesn't apply until then
+        self.start_transition();
+        self.scheduled = None;
+    }
+
+    /// Fade from whatever is currently on screen, so changes during a running fade don't jump.
+    fn start_transition(&mut self) {
+        if self.transition_duration.is_zero() {
+            self.transition = None;
+            return;
+        }
+
+        // outputs that had their own fade running, or have overrides, might differ from the rest
+        let mut keys = self
+            .outputs
+            .keys()
+            .chain(self.suspended.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(transition) = self.transition.as_ref() {
+            keys.extend(transition.from_outputs.keys().cloned());
+        }
+        let from_outputs = keys
+            .into_iter()
+            .map(|key| {
+                let adaptation = self.adaptation(&key);
+                (key, adaptation)
+            })
+            .collect();
+
+        self.transition = Some(Transition {
+            start: Instant::now(),
+            from: self.adaptation_without_override(),
+            from_outputs,
+        });
+    }
+
+    pub fn set_tint_captures(&mut self, sources: impl IntoIterator<Item = CaptureSource>) {
+        self.tint_captures = sources.into_iter().collect();
+    }
+
+    pub fn set_preserve_luminance(&mut self, preserve_luminance: bool) {
+        self.preserve_luminance = preserve_luminance;
+    }
+
+    /// Returns `false` if `dim` is outside of `0.0..=MAX_DIM`.
+    pub fn set_dim(&mut self, dim: f32) -> bool {
+        if !(0.0..=MAX_DIM).contains(&dim) {
+            return false;
+        }
+        if self.dim != dim {
+            self.start_transition();
+            self.dim = dim;
+        }
+        true
+    }
+
+    pub fn set_pause_for(&mut self, kinds: impl IntoIterator<Item = ContentKind>) {
+        self.pause_for = kinds.into_iter().collect();
+    }
+
+    /// Outputs night light is paused on, and the fullscreen content that paused it.
+    pub fn suspended(&self) -> &BTreeMap<OutputKey, ContentKind> {
+        &self.suspended
+    }
+
+    /// Fades night light out on the `suspended` outputs, and back in on all others.
+    ///
+    /// Returns `true` if that changed anything.
+    pub fn set_suspended(&mut self, suspended: BTreeMap<OutputKey, ContentKind>) -> bool {
+        if self.suspended == suspended {
+            return false;
+        }
+        self.start_transition();
+        self.suspended = suspended;
+        true
+    }
+
+    /// Records whether a gamma-control client sets the gamma ramp of `output`.
+    pub fn set_gamma_client(&mut self, output: OutputKey, active: bool) {
+        if active {
+            self.gamma_clients.insert(output);
+        } else {
+            self.gamma_clients.remove(&output);
+        }
+    }
+
+    /// Tint to apply to a capture of `source` showing `output`, `None` if it stays untinted.
+    ///
+    /// Outputs handed to a gamma-control client show no night light, and neither do their captures.
+    pub fn capture_adaptation(
+        &self,
+        source: CaptureSource,
+        output: &OutputKey,
+    ) -> Option<Adaptation> {
+        if !self.tint_captures.contains(&source) || self.gamma_clients.contains(output) {
+            return None;
+        }
+        Some(self.adaptation(output)).filter(|adaptation| !adaptation.is_neutral())
+    }
+
+    pub fn is_inhibited(&self) -> bool {
+        !self.inhibitors.is_empty()
+    }
+
+    /// Turns night light off until the returned cookie is passed to `uninhibit`,
+    /// without touching the user's settings.
+    pub fn inhibit(&mut self, owner: String, reason: String) -> u32 {
+        // `0` is never handed out, so clients can use it as "no cookie"
+        self.last_cookie = self.last_cookie.wrapping_add(1).max(1);
+        let cookie = self.last_cookie;
+        self.update_inhibitors(|inhibitors| {
+            inhibitors.insert(cookie, Inhibitor { owner, reason });
+        });
+        cookie
+    }
+
+    /// Returns `false` if `cookie` is unknown or belongs to someone other than `owner`.
+    pub fn uninhibit(&mut self, cookie: u32, owner: &str) -> bool {
+        if self
+            .inhibitors
+            .get(&cookie)
+            .is_none_or(|inhibitor| inhibitor.owner != owner)
+        {
+            return false;
+        }
+        self.update_inhibitors(|inhibitors| {
+            inhibitors.remove(&cookie);
+        });
+        true
+    }
+
+    /// Drops inhibitors of clients that are gone, returns `true` if any were dropped.
+    pub fn retain_inhibitors(&mut self, mut alive: impl FnMut(&str) -> bool) -> bool {
+        let count = self.inhibitors.len();
+        self.update_inhibitors(|inhibitors| {
+            inhibitors.retain(|_, inhibitor| alive(&inhibitor.owner));
+        });
+        self.inhibitors.len() != count
+    }
+
+    fn update_inhibitors(&mut self, update: impl FnOnce(&mut BTreeMap<u32, Inhibitor>)) {
+        let mut inhibitors = self.inhibitors.clone();
+        update(&mut inhibitors);
+        // fade from the current state, before it changes
+        if inhibitors.is_empty() != self.inhibitors.is_empty() {
+            self.start_transition();
+        }
+        self.inhibitors = inhibitors;
+    }
+
+    /// Whether the night light currently leaves `output` untouched.
+    pub fn is_noop(&self, output: &OutputKey) -> bool {
+        !self.output_active(output) && !self.is_animating()
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.transition
+            .as_ref()
+            .is_some_and(|transition| transition.start.elapsed() < self.transition_duration)
+    }
+
+    /// What the current settings of `output` fade towards.
+    pub fn target_adaptation(&self, output: &OutputKey) -> Adaptation {
+        if self.output_active(output) {
+            self.tint(self.output_temperature(output))
+        } else {
+            self.untinted()
+        }
+    }
+
+    /// How to transform the colors of `output` right now.
+    pub fn adaptation(&self, output: &OutputKey) -> Adaptation {
+        let from = self.transition.as_ref().map(|transition| {
+            transition
+                .from_outputs
+                .get(output)
+                .copied()
+                .unwrap_or(transition.from)
+        });
+        self.fade(from, self.target_adaptation(output))
+    }
+
+    /// Like `adaptation`, for an output we know nothing about.
+    fn adaptation_without_override(&self) -> Adaptation {
+        let target = if self.enabled && !self.is_inhibited() {
+            self.tint(self.temperature)
+        } else {
+            self.untinted()
+        };
+        self.fade(
+            self.transition.as_ref().map(|transition| transition.from),
+            target,
+        )
+    }
+
+    fn tint(&self, kelvin: u32) -> Adaptation {
+        let strength = self.ramp_strength();
+        let kelvin = NEUTRAL_TEMPERATURE as f32 - (NEUTRAL_TEMPERATURE - kelvin) as f32 * strength;
+        Adaptation {
+            gains: temperature_to_rgb(kelvin.round() as u32),
+            preserve_luminance: self.preserve_luminance,
+            brightness: 1. - self.dim * strength,
+        }
+    }
+
+    fn untinted(&self) -> Adaptation {
+        Adaptation {
+            preserve_luminance: self.preserve_luminance,
+            ..Adaptation::NEUTRAL
+        }
+    }
+
+    fn fade(&self, from: Option<Adaptation>, target: Adaptation) -> Adaptation {
+        match (from, self.transition.as_ref()) {
+            (Some(from), Some(transition))
+                if transition.start.elapsed() < self.transition_duration =>
+            {
+                let percentage = transition.start.elapsed().as_secs_f32()
+                    / self.transition_duration.as_secs_f32();
+                Adaptation {
+                    gains: [0, 1, 2]
+                        .map(|i| ease(EaseInOutCubic, from.gains[i], target.gains[i], percentage)),
+                    brightness: ease(
+                        EaseInOutCubic,
+                        from.brightness,
+                        target.brightness,
+                        percentage,
+                    ),
+                    ..target
+                }
+            }
+            _ => target,
+        }
+    }
+}
+
+fn valid_location(latitude: f64, longitude: f64) -> bool {
+    (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)
+}
+
+impl State {
+    /// Persists the current night light settings and pushes them to the backend.
+    pub fn update_night_light(&mut self) {
+        let now = schedule::local_now(&mut self.common.local_offset);
+        let config = {
+            let mut night_light = self.common.night_light.lock();
+            night_light.apply_schedule(&now);
+            night_light.config()
+        };
+        if *self.common.config.dynamic_conf.night_light() != config {
+            *self.common.config.dynamic_conf.night_light_mut() = config;
+        }
+
+        schedule::arm_override_timer(self);
+
+        self.backend.update_night_light();
+        if let Some(signals) = self.common.night_light_signals.as_ref() {
+            let _ = signals.send(());
+        }
+    }
+}
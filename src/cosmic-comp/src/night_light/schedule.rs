@@ -0,0 +1,313 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{str::FromStr, time::Duration};
+
+use calloop::{
+    LoopHandle, RegistrationToken,
+    timer::{TimeoutAction, Timer},
+};
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, UtcOffset};
+use tracing::warn;
+
+use super::OverrideExpiry;
+use crate::state::State;
+
+pub const MINUTES_PER_DAY: u16 = 24 * 60;
+pub const DEFAULT_SCHEDULE_FROM: u16 = 19 * 60;
+pub const DEFAULT_SCHEDULE_TO: u16 = 7 * 60;
+/// Longest ramp in minutes, longer ones would overlap on short nights.
+pub const MAX_SCHEDULE_RAMP: u16 = 3 * 60;
+/// How often the schedule is re-evaluated while ramping.
+const RAMP_INTERVAL: Duration = Duration::from_secs(10);
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleMode {
+    /// Night light is only changed manually
+    #[default]
+    Disabled,
+    /// Night light is on between `ScheduleFrom` and `ScheduleTo`
+    Custom,
+    /// Night light is on from civil dusk to civil dawn at the configured location
+    Solar,
+}
+
+impl ScheduleMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleMode::Disabled => "disabled",
+            ScheduleMode::Custom => "custom",
+            ScheduleMode::Solar => "solar",
+        }
+    }
+}
+
+impl FromStr for ScheduleMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disabled" => Ok(ScheduleMode::Disabled),
+            "custom" => Ok(ScheduleMode::Custom),
+            "solar" => Ok(ScheduleMode::Solar),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The wall clock in the local time zone as of now, so DST and time zone changes apply right away.
+///
+/// Updates `offset`, and falls back to it if the time zone can't be determined.
+pub fn local_now(offset: &mut UtcOffset) -> OffsetDateTime {
+    let now = OffsetDateTime::now_utc();
+    // SAFETY: `TZ` is never modified by the compositor, `tzset` rereads `/etc/localtime`
+    // in case it was replaced since the last evaluation
+    unsafe { tzset() };
+    if let Ok(current) = UtcOffset::local_offset_at(now) {
+        *offset = current;
+    }
+    now.to_offset(*offset)
+}
+
+unsafe extern "C" {
+    // missing from the libc crate
+    fn tzset();
+}
+
+/// Minutes since local midnight.
+pub fn minute_of_day(now: &OffsetDateTime) -> u16 {
+    now.hour() as u16 * 60 + now.minute() as u16
+}
+
+/// Whether `minute` lies in `from..to`, wrapping around midnight if `to` is earlier than `from`.
+pub fn in_window(minute: u16, from: u16, to: u16) -> bool {
+    if from <= to {
+        (from..to).contains(&minute)
+    } else {
+        minute >= from || minute < to
+    }
+}
+
+/// Whether `now` lies in the custom schedule from `from` to `to`,
+/// and how long ago the schedule last flipped.
+pub fn custom_phase(now: &OffsetDateTime, from: u16, to: u16) -> (bool, Duration) {
+    if from == to {
+        return (false, Duration::MAX);
+    }
+    let minute = minute_of_day(now);
+    let night = in_window(minute, from, to);
+    let transition = if night { from } else { to } as u32 * 60;
+    let second = minute as u32 * 60 + now.second() as u32;
+    let since = (second + SECONDS_PER_DAY - transition) % SECONDS_PER_DAY;
+    (night, Duration::from_secs(since as u64))
+}
+
+/// How far night light is faded in, from `0.0` at day to `1.0` at night.
+///
+/// The ramp starts at each transition, so night light fades in over `ramp` after the evening
+/// transition and out over `ramp` after the morning one. It only depends on the wall clock,
+/// which makes it pick up at the right point after suspend or a restart.
+pub fn ramp_strength(night: bool, since_transition: Duration, ramp: Duration) -> f32 {
+    if since_transition >= ramp {
+        return if night { 1. } else { 0. };
+    }
+    let progress = since_transition.as_secs_f32() / ramp.as_secs_f32();
+    if night { progress } else { 1. - progress }
+}
+
+/// Re-evaluates the schedule at the start of every minute, and more often while ramping.
+///
+/// The timer runs on the monotonic clock, which stops during suspend,
+/// so we never sleep longer than a minute and always compare against the wall clock.
+pub fn init(evlh: &LoopHandle<'static, State>) -> anyhow::Result<RegistrationToken> {
+    evlh.insert_source(Timer::immediate(), |_, _, state| {
+        let now = local_now(&mut state.common.local_offset);
+        if state.common.night_light.lock().apply_schedule(&now) {
+            state.update_night_light();
+        }
+
+        let next_minute = Duration::from_secs(60 - now.second() as u64);
+        if state.common.night_light.lock().is_ramping() {
+            TimeoutAction::ToDuration(next_minute.min(RAMP_INTERVAL))
+        } else {
+            TimeoutAction::ToDuration(next_minute)
+        }
+    })
+    .map_err(|err| anyhow::anyhow!("Failed to insert night light schedule timer: {}", err.error))
+}
+
+/// Wakes up when a timed override expires, the schedule timer is too coarse for short overrides.
+///
+/// Re-armed on every night light update, as that is how new overrides arrive from D-Bus.
+pub fn arm_override_timer(state: &mut State) {
+    if let Some(token) = state.common.night_light_override_timer.take() {
+        state.common.event_loop_handle.remove(token);
+    }
+    let Some(at) = state
+        .common
+        .night_light
+        .lock()
+        .timed_override()
+        .and_then(|o| match o.expiry {
+            OverrideExpiry::At(at) => Some(at),
+            OverrideExpiry::NextTransition => None,
+        })
+    else {
+        return;
+    };
+
+    let delay = Duration::try_from(at - OffsetDateTime::now_utc()).unwrap_or(Duration::ZERO);
+    match state.common.event_loop_handle.insert_source(
+        Timer::from_duration(delay),
+        |_, _, state| {
+            // don't let the update remove the source we are running from
+            state.common.night_light_override_timer = None;
+            state.update_night_light();
+            TimeoutAction::Drop
+        },
+    ) {
+        Ok(token) => state.common.night_light_override_timer = Some(token),
+        Err(err) => warn!("Failed to insert night light override timer: {}", err.error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ScheduleMode, custom_phase, ramp_strength};
+    use std::time::Duration;
+    use time::{Date, Month, OffsetDateTime, Time};
+
+    use crate::{
+        config::NightLightConfig,
+        night_light::{NightLightState, OverrideExpiry},
+    };
+
+    fn at(hour: u8, minute: u8) -> OffsetDateTime {
+        Date::from_calendar_date(2024, Month::March, 1)
+            .unwrap()
+            .with_time(Time::from_hms(hour, minute, 0).unwrap())
+            .assume_utc()
+    }
+
+    #[test]
+    fn test_custom_phase() {
+        let (from, to) = (19 * 60, 7 * 60);
+        assert_eq!(
+            custom_phase(&at(19, 30), from, to),
+            (true, Duration::from_secs(30 * 60))
+        );
+        // wraps around midnight
+        assert_eq!(
+            custom_phase(&at(1, 0), from, to),
+            (true, Duration::from_secs(6 * 60 * 60))
+        );
+        assert_eq!(
+            custom_phase(&at(7, 15), from, to),
+            (false, Duration::from_secs(15 * 60))
+        );
+        assert!(!custom_phase(&at(12, 0), 600, 600).0);
+    }
+
+    #[test]
+    fn test_ramp() {
+        let ramp = Duration::from_secs(40 * 60);
+        let strength = |now| {
+            let (night, since) = custom_phase(&now, 19 * 60, 7 * 60);
+            ramp_strength(night, since, ramp)
+        };
+        assert_eq!(strength(at(18, 59)), 0.);
+        assert_eq!(strength(at(19, 0)), 0.);
+        assert!((strength(at(19, 10)) - 0.25).abs() < 1e-6);
+        assert_eq!(strength(at(19, 40)), 1.);
+        assert_eq!(strength(at(3, 0)), 1.);
+        assert!((strength(at(7, 30)) - 0.25).abs() < 1e-6);
+        assert_eq!(strength(at(7, 40)), 0.);
+
+        assert_eq!(ramp_strength(true, Duration::ZERO, Duration::ZERO), 1.);
+        assert_eq!(ramp_strength(false, Duration::ZERO, Duration::ZERO), 0.);
+    }
+
+    #[test]
+    fn test_manual_change_survives_restart() {
+        let mut state = NightLightState::new(&NightLightConfig {
+            schedule_mode: ScheduleMode::Custom,
+            ..Default::default()
+        });
+        state.apply_schedule(&at(20, 0));
+        assert!(state.enabled);
+
+        // turned off by hand in the evening, stays off after a restart
+        state.set_enabled(false);
+        let mut state = NightLightState::new(&state.config());
+        state.apply_schedule(&at(22, 0));
+        assert!(!state.enabled);
+        // until the schedule flips twice
+        state.apply_schedule(&at(8, 0));
+        state.apply_schedule(&at(20, 0));
+        assert!(state.enabled);
+
+        state.set_override(false, OverrideExpiry::NextTransition);
+        let mut state = NightLightState::new(&state.config());
+        state.apply_schedule(&at(22, 0));
+        assert!(!state.enabled);
+        assert!(state.timed_override().is_some());
+        state.apply_schedule(&at(8, 0));
+        assert!(state.timed_override().is_none());
+    }
+
+    #[test]
+    fn test_manual_change_survives_morning_ramp() {
+        let mut state = NightLightState::new(&NightLightConfig {
+            schedule_mode: ScheduleMode::Custom,
+            schedule_ramp: 30,
+            ..Default::default()
+        });
+        state.apply_schedule(&at(20, 0));
+        state.set_enabled(false);
+        state.apply_schedule(&at(23, 0));
+
+        // the schedule still wants night light on while it fades out
+        let morning = at(7, 0) + time::Duration::days(1) + time::Duration::seconds(30);
+        state.apply_schedule(&morning);
+        assert!(!state.enabled);
+
+        let mut state = NightLightState::new(&NightLightConfig {
+            schedule_mode: ScheduleMode::Custom,
+            schedule_ramp: 30,
+            ..Default::default()
+        });
+        state.apply_schedule(&at(20, 0));
+        state.set_override(false, OverrideExpiry::NextTransition);
+        state.apply_schedule(&morning);
+        assert!(!state.enabled);
+        assert!(state.timed_override().is_some());
+        // ends once the schedule turns night light off as well
+        state.apply_schedule(&(at(7, 30) + time::Duration::days(1)));
+        assert!(!state.enabled);
+        assert!(state.timed_override().is_none());
+    }
+
+    #[test]
+    fn test_manual_change_expires_during_downtime() {
+        let next_day = |days, hour, minute| at(hour, minute) + time::Duration::days(days);
+        let mut state = NightLightState::new(&NightLightConfig {
+            schedule_mode: ScheduleMode::Custom,
+            ..Default::default()
+        });
+        state.apply_schedule(&at(20, 0));
+        state.set_enabled(false);
+
+        // off until the next evening, the schedule turned off and on again meanwhile
+        let mut state = NightLightState::new(&state.config());
+        state.apply_schedule(&next_day(1, 21, 0));
+        assert!(state.enabled);
+
+        state.set_override(false, OverrideExpiry::NextTransition);
+        let mut state = NightLightState::new(&state.config());
+        state.apply_schedule(&next_day(2, 21, 0));
+        assert!(state.enabled);
+        assert!(state.timed_override().is_none());
+    }
+}
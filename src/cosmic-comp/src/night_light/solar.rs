@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Civil twilight times, following the NOAA solar calculator.
+//!
+//! See <https://gml.noaa.gov/grad/solcalc/calcdetails.html>.
+//! Accurate to about a minute between the polar circles, which is plenty for a night light.
+
+use time::{Date, Duration, OffsetDateTime};
+
+/// Zenith angle of the sun at civil dawn and dusk (6° below the horizon).
+const CIVIL_ZENITH: f64 = 96.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Twilight {
+    /// Civil dawn and dusk of the day
+    Times {
+        dawn: OffsetDateTime,
+        dusk: OffsetDateTime,
+    },
+    /// The sun never sets more than 6° below the horizon
+    PolarDay,
+    /// The sun never rises to 6° below the horizon
+    PolarNight,
+}
+
+impl Twilight {
+    pub fn is_night(&self, now: &OffsetDateTime) -> bool {
+        match self {
+            Twilight::Times { dawn, dusk } => now < dawn || now >= dusk,
+            Twilight::PolarDay => false,
+            Twilight::PolarNight => true,
+        }
+    }
+
+    /// Like `is_night`, plus how long ago the last dawn or dusk was, for the schedule ramp.
+    pub fn phase(&self, now: &OffsetDateTime) -> (bool, std::time::Duration) {
+        let since = |transition: OffsetDateTime| {
+            std::time::Duration::try_from(*now - transition).unwrap_or(std::time::Duration::MAX)
+        };
+        match *self {
+            // yesterday's dusk was close enough to today's
+            Twilight::Times { dawn, dusk } if *now < dawn => (true, since(dusk - Duration::DAY)),
+            Twilight::Times { dusk, .. } if *now >= dusk => (true, since(dusk)),
+            Twilight::Times { dawn, .. } => (false, since(dawn)),
+            Twilight::PolarDay | Twilight::PolarNight => {
+                (self.is_night(now), std::time::Duration::MAX)
+            }
+        }
+    }
+}
+
+/// Computes civil dawn and dusk for `date` at the given position (degrees, north and east positive).
+pub fn civil_twilight(date: Date, latitude: f64, longitude: f64) -> Twilight {
+    let dawn = match twilight_minutes(date, latitude, longitude, true) {
+        Ok(minutes) => minutes,
+        Err(polar) => return polar,
+    };
+    let dusk = match twilight_minutes(date, latitude, longitude, false) {
+        Ok(minutes) => minutes,
+        Err(polar) => return polar,
+    };
+
+    let midnight = date.midnight().assume_utc();
+    Twilight::Times {
+        dawn: midnight + Duration::seconds_f64(dawn * 60.),
+        dusk: midnight + Duration::seconds_f64(dusk * 60.),
+    }
+}
+
+/// Minutes after UTC midnight of `date`, refined a few times,
+/// because declination and equation of time depend on the result.
+fn twilight_minutes(
+    date: Date,
+    latitude: f64,
+    longitude: f64,
+    dawn: bool,
+) -> Result<f64, Twilight> {
+    // start at solar noon
+    let mut minutes = 720. - 4. * longitude;
+    for _ in 0..3 {
+        let julian_day = date.to_julian_day() as f64 - 0.5 + minutes / 1440.;
+        let (declination, equation_of_time) = sun_position(julian_day);
+
+        let latitude = latitude.to_radians();
+        let cos_hour_angle = CIVIL_ZENITH.to_radians().cos() / (latitude.cos() * declination.cos())
+            - latitude.tan() * declination.tan();
+        if cos_hour_angle > 1. {
+            return Err(Twilight::PolarNight);
+        }
+        if cos_hour_angle < -1. {
+            return Err(Twilight::PolarDay);
+        }
+
+        let hour_angle = cos_hour_angle.acos().to_degrees();
+        let noon = 720. - 4. * longitude - equation_of_time;
+        minutes = if dawn {
+            noon - 4. * hour_angle
+        } else {
+            noon + 4. * hour_angle
+        };
+    }
+    Ok(minutes)
+}
+
+/// Declination of the sun (radians) and equation of time (minutes) at the given julian day.
+fn sun_position(julian_day: f64) -> (f64, f64) {
+    let t = (julian_day - 2451545.) / 36525.;
+
+    let mean_long = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.);
+    let mean_anomaly = 357.52911 + t * (35999.05029 - 0.0001537 * t);
+    let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+
+    let m = mean_anomaly.to_radians();
+    let center = m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2. * m).sin() * (0.019993 - 0.000101 * t)
+        + (3. * m).sin() * 0.000289;
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_long = mean_long + center - 0.00569 - 0.00478 * omega.sin();
+
+    let mean_obliquity =
+        23. + (26. + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.) / 60.;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+
+    let declination = (obliquity.sin() * apparent_long.to_radians().sin()).asin();
+
+    let y = (obliquity / 2.).tan().powi(2);
+    let l = mean_long.to_radians();
+    let equation_of_time = 4.
+        * (y * (2. * l).sin() - 2. * eccentricity * m.sin()
+            + 4. * eccentricity * y * m.sin() * (2. * l).cos()
+            - 0.5 * y * y * (4. * l).sin()
+            - 1.25 * eccentricity * eccentricity * (2. * m).sin())
+        .to_degrees();
+
+    (declination, equation_of_time)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Twilight, civil_twilight};
+    use time::{Date, Month, OffsetDateTime, UtcOffset};
+
+    /// Asserts dawn and dusk match the almanac within two minutes.
+    fn assert_twilight(
+        twilight: Twilight,
+        offset_hours: i8,
+        (dawn_h, dawn_m): (u8, u8),
+        (dusk_h, dusk_m): (u8, u8),
+    ) {
+        let Twilight::Times { dawn, dusk } = twilight else {
+            panic!("Expected civil twilight, got {:?}", twilight);
+        };
+        let offset = UtcOffset::from_hms(offset_hours, 0, 0).unwrap();
+        let minutes = |time: OffsetDateTime| {
+            let time = time.to_offset(offset);
+            time.hour() as i32 * 60 + time.minute() as i32
+        };
+
+        let dawn = minutes(dawn);
+        let dusk = minutes(dusk);
+        let expected_dawn = dawn_h as i32 * 60 + dawn_m as i32;
+        let expected_dusk = dusk_h as i32 * 60 + dusk_m as i32;
+        assert!(
+            (dawn - expected_dawn).abs() <= 2,
+            "dawn at {dawn}, expected {expected_dawn}"
+        );
+        assert!(
+            (dusk - expected_dusk).abs() <= 2,
+            "dusk at {dusk}, expected {expected_dusk}"
+        );
+    }
+
+    #[test]
+    fn test_mid_latitudes() {
+        let june = Date::from_calendar_date(2024, Month::June, 21).unwrap();
+        let december = Date::from_calendar_date(2024, Month::December, 21).unwrap();
+
+        // London
+        assert_twilight(civil_twilight(june, 51.5074, -0.1278), 1, (3, 56), (22, 8));
+        assert_twilight(
+            civil_twilight(december, 51.5074, -0.1278),
+            0,
+            (7, 25),
+            (16, 33),
+        );
+        // New York
+        assert_twilight(
+            civil_twilight(december, 40.7128, -74.0060),
+            -5,
+            (6, 46),
+            (17, 3),
+        );
+        // Sydney
+        assert_twilight(
+            civil_twilight(december, -33.8688, 151.2093),
+            11,
+            (5, 12),
+            (20, 34),
+        );
+    }
+
+    #[test]
+    fn test_equinox_on_equator() {
+        let equinox = Date::from_calendar_date(2024, Month::March, 20).unwrap();
+        assert_twilight(civil_twilight(equinox, 0., 0.), 0, (5, 43), (18, 31));
+    }
+
+    #[test]
+    fn test_polar() {
+        let june = Date::from_calendar_date(2024, Month::June, 21).unwrap();
+        let december = Date::from_calendar_date(2024, Month::December, 21).unwrap();
+
+        // Longyearbyen
+        assert_eq!(civil_twilight(june, 78.22, 15.65), Twilight::PolarDay);
+        assert_eq!(civil_twilight(december, 78.22, 15.65), Twilight::PolarNight);
+        // Tromsø has midnight sun, but still a few hours of civil twilight in the polar night
+        assert_eq!(civil_twilight(june, 69.65, 18.96), Twilight::PolarDay);
+        assert_twilight(civil_twilight(december, 69.65, 18.96), 1, (9, 32), (13, 53));
+        // and the other way round in the south
+        assert_eq!(civil_twilight(december, -77.85, 166.67), Twilight::PolarDay);
+    }
+
+    #[test]
+    fn test_is_night() {
+        let december = Date::from_calendar_date(2024, Month::December, 21).unwrap();
+        let twilight = civil_twilight(december, 51.5074, -0.1278);
+        let at = |hour, minute| december.with_hms(hour, minute, 0).unwrap().assume_utc();
+
+        assert!(twilight.is_night(&at(3, 0)));
+        assert!(!twilight.is_night(&at(12, 0)));
+        assert!(twilight.is_night(&at(20, 0)));
+        assert!(Twilight::PolarNight.is_night(&at(12, 0)));
+        assert!(!Twilight::PolarDay.is_night(&at(0, 0)));
+    }
+}
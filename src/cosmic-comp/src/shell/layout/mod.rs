@@ -54,13 +54,14 @@ pub fn is_dialog(window: &CosmicSurface) -> bool {
     false
 }
 
+/// Window rules from `com.system76.CosmicSettings.WindowRules`, matched by app_id and title
 #[derive(Debug, Clone, Default)]
-pub struct TilingExceptions {
+pub struct WindowExceptions {
     app_ids: RegexSet,
     titles: RegexSet,
 }
 
-impl TilingExceptions {
+impl WindowExceptions {
     pub fn new<'a, I>(exceptions_config: I) -> Self
     where
         I: Iterator<Item = &'a ApplicationException>,
@@ -87,17 +88,25 @@ impl TilingExceptions {
             titles: RegexSet::new(titles).unwrap(),
         }
     }
-}
 
-pub fn has_floating_exception(exceptions: &TilingExceptions, window: &CosmicSurface) -> bool {
-    // else take a look at our exceptions
-    let appid_matches = exceptions.app_ids.matches(&window.app_id());
-    let title_matches = exceptions.titles.matches(&window.title());
-    for idx in appid_matches.into_iter() {
-        if title_matches.matched(idx) {
-            return true;
+    pub fn is_empty(&self) -> bool {
+        self.app_ids.is_empty()
+    }
+
+    /// Whether any rule matches both the app_id and the title of `window`
+    pub fn matches(&self, window: &CosmicSurface) -> bool {
+        let appid_matches = self.app_ids.matches(&window.app_id());
+        let title_matches = self.titles.matches(&window.title());
+        for idx in appid_matches.into_iter() {
+            if title_matches.matched(idx) {
+                return true;
+            }
         }
+
+        false
     }
+}
 
-    false
+pub fn has_floating_exception(exceptions: &WindowExceptions, window: &CosmicSurface) -> bool {
+    exceptions.matches(window)
 }
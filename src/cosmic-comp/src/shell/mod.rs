@@ -2,7 +2,7 @@ use calloop::LoopHandle;
 use focus::target::WindowGroup;
 use grabs::{MenuAlignment, SeatMoveGrabState};
 use indexmap::IndexMap;
-use layout::TilingExceptions;
+use layout::WindowExceptions;
 use std::{
     collections::HashMap,
     sync::{Mutex, atomic::Ordering},
@@ -64,7 +64,10 @@ use tracing::error;
 use crate::{
     backend::render::animations::spring::{Spring, SpringParams},
     config::Config,
-    utils::{prelude::*, quirks::WORKSPACE_OVERVIEW_NAMESPACE},
+    utils::{
+        prelude::*,
+        quirks::{WORKSPACE_OVERVIEW_NAMESPACE, workspace_overview_is_open},
+    },
     wayland::{
         handlers::{
             toplevel_management::minimize_rectangle, xdg_activation::ActivationContext,
@@ -288,7 +291,8 @@ pub struct Shell {
     resize_indicator: Option<ResizeIndicator>,
     zoom_state: Option<ZoomState>,
     appearance_conf: AppearanceConfig,
-    tiling_exceptions: TilingExceptions,
+    tiling_exceptions: WindowExceptions,
+    night_light_exceptions: WindowExceptions,
 
     #[cfg(feature = "debug")]
     pub debug_active: bool,
@@ -1445,6 +1449,8 @@ impl Common {
     }
 
     pub fn remove_output(&mut self, output: &Output) {
+        self.gamma_control_state.output_removed(output);
+
         let mut shell = self.shell.write();
         let shell_ref = &mut *shell;
         shell_ref.workspaces.remove_output(
@@ -1574,7 +1580,9 @@ impl Shell {
     pub fn new(config: &Config) -> Self {
         let theme = cosmic::theme::system_preference();
 
-        let tiling_exceptions = layout::TilingExceptions::new(config.tiling_exceptions.iter());
+        let tiling_exceptions = layout::WindowExceptions::new(config.tiling_exceptions.iter());
+        let night_light_exceptions =
+            layout::WindowExceptions::new(config.night_light_exceptions.iter());
 
         Shell {
             workspaces: Workspaces::new(config, theme.clone()),
@@ -1598,6 +1606,7 @@ impl Shell {
             appearance_conf: config.cosmic_conf.appearance_settings.clone(),
             zoom_state: None,
             tiling_exceptions,
+            night_light_exceptions,
 
             #[cfg(feature = "debug")]
             debug_active: false,
@@ -4834,7 +4843,76 @@ impl Shell {
     where
         I: Iterator<Item = &'a ApplicationException>,
     {
-        self.tiling_exceptions = layout::TilingExceptions::new(exceptions);
+        self.tiling_exceptions = layout::WindowExceptions::new(exceptions);
+    }
+
+    pub fn update_night_light_exceptions<'a, I>(&mut self, exceptions: I)
+    where
+        I: Iterator<Item = &'a ApplicationException>,
+    {
+        self.night_light_exceptions = layout::WindowExceptions::new(exceptions);
+    }
+
+    /// Whether the night light window rules exempt `window`.
+    pub fn is_night_light_exempt(&self, window: &CosmicSurface) -> bool {
+        self.night_light_exceptions.matches(window)
+    }
+
+    /// Visible areas of windows exempt from night light, relative to `output`.
+    ///
+    /// Anything stacked above an exempt window is cut out, so the areas never overlap.
+    pub fn night_light_exempt_regions(&self, output: &Output) -> Vec<Rectangle<i32, Logical>> {
+        if self.night_light_exceptions.is_empty()
+            || self.session_lock.is_some()
+            || workspace_overview_is_open(output)
+        {
+            return Vec::new();
+        }
+        // windows move during workspace switches, keep it simple and tint everything
+        let Some((None, workspace)) = self.workspaces.active(output) else {
+            return Vec::new();
+        };
+
+        // from top to bottom
+        let windows = if let Some(fullscreen) = workspace.get_fullscreen() {
+            let geometry = workspace.fullscreen_geometry().unwrap().as_logical();
+            vec![(geometry, fullscreen.clone())]
+        } else {
+            let sticky = self
+                .workspaces
+                .sets
+                .get(output)
+                .into_iter()
+                .flat_map(|set| {
+                    set.sticky_layer.mapped().filter_map(|mapped| {
+                        let geometry = set.sticky_layer.element_geometry(mapped)?;
+                        Some((geometry.as_logical(), mapped.active_window()))
+                    })
+                });
+            let workspace = workspace.mapped().filter_map(|mapped| {
+                let geometry = workspace.element_geometry(mapped)?;
+                Some((geometry.as_logical(), mapped.active_window()))
+            });
+            sticky.chain(workspace).collect::<Vec<_>>()
+        };
+
+        let layer_map = layer_map_for_output(output);
+        let mut above = [Layer::Overlay, Layer::Top]
+            .into_iter()
+            .flat_map(|layer| layer_map.layers_on(layer))
+            .filter_map(|surface| layer_map.layer_geometry(surface))
+            .collect::<Vec<_>>();
+        let mut regions = Vec::new();
+        for (geometry, window) in windows {
+            if self.night_light_exceptions.matches(&window) {
+                regions.extend(Rectangle::subtract_rects_many(
+                    [geometry],
+                    above.iter().copied(),
+                ));
+            }
+            above.push(geometry);
+        }
+        regions
     }
 
     pub fn take_presentation_feedback(
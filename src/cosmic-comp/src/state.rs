@@ -18,6 +18,7 @@ use crate::{
             a11y::A11yState,
             corner_radius::CornerRadiusState,
             drm::WlDrmState,
+            gamma_control::GammaControlState,
             image_capture_source::CosmicImageCaptureSourceState,
             output_configuration::OutputConfigurationState,
             output_power::OutputPowerState,
@@ -74,6 +75,7 @@ use smithay::{
     wayland::{
         alpha_modifier::AlphaModifierState,
         compositor::{CompositorClientState, CompositorState, SurfaceData},
+        content_type::ContentTypeState,
         cursor_shape::CursorShapeManagerState,
         dmabuf::{DmabufFeedback, DmabufGlobal, DmabufState},
         fixes::FixesState,
@@ -254,6 +256,7 @@ pub struct Common {
     pub data_device_state: DataDeviceState,
     pub dmabuf_state: DmabufState,
     pub fractional_scale_state: FractionalScaleManagerState,
+    pub gamma_control_state: GammaControlState,
     pub keyboard_shortcuts_inhibit_state: KeyboardShortcutsInhibitState,
     pub output_state: OutputManagerState,
     pub output_configuration_state: OutputConfigurationState<State>,
@@ -297,7 +300,11 @@ pub struct Common {
     #[cfg(feature = "systemd")]
     pub inhibit_lid_fd: Option<OwnedFd>,
 
-    pub night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+    pub night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
+    /// Asks the D-Bus thread to announce night light changes
+    pub night_light_signals: Option<std::sync::mpsc::Sender<()>>,
+    /// Ends the active timed night light override
+    pub night_light_override_timer: Option<RegistrationToken>,
 }
 
 #[derive(Debug)]
@@ -430,6 +437,17 @@ impl BackendData {
         }
     }
 
+    pub fn update_night_light(&mut self) {
+        match self {
+            BackendData::Kms(state) => state.update_night_light(),
+            // Winit renders continuously and picks up the new tint on the next frame.
+            BackendData::Winit(_) => {}
+            BackendData::X11(state) => state.update_night_light(),
+            // The schedule may fire before a backend is set up, it will render the current state anyway.
+            BackendData::Unset => {}
+        }
+    }
+
     pub fn lock(&mut self) -> LockedBackend<'_> {
         match self {
             BackendData::Kms(state) => LockedBackend::Kms(state.lock_devices()),
@@ -488,7 +506,7 @@ impl LockedBackend<'_> {
         shell: Arc<parking_lot::RwLock<Shell>>,
         workspace_state: &mut WorkspaceUpdateGuard<'_, State>,
         xdg_activation_state: &XdgActivationState,
-        night_light: std::sync::Arc<parking_lot::Mutex<crate::dbus::night_light::NightLightState>>,
+        night_light: std::sync::Arc<parking_lot::Mutex<crate::night_light::NightLightState>>,
         startup_done: Arc<AtomicBool>,
         clock: &Clock<Monotonic>,
     ) -> Result<(), anyhow::Error> {
@@ -644,6 +662,7 @@ impl State {
         let data_device_state = DataDeviceState::new::<Self>(dh);
         let dmabuf_state = DmabufState::new();
         let fractional_scale_state = FractionalScaleManagerState::new::<State>(dh);
+        let gamma_control_state = GammaControlState::new::<Self, _>(dh, client_not_sandboxed);
         let keyboard_shortcuts_inhibit_state = KeyboardShortcutsInhibitState::new::<Self>(dh);
         let output_state = OutputManagerState::new_with_xdg_output::<Self>(dh);
         let output_configuration_state =
@@ -681,6 +700,7 @@ impl State {
         TextInputManagerState::new::<Self>(dh);
         VirtualKeyboardManagerState::new::<State, _>(dh, client_not_sandboxed);
         AlphaModifierState::new::<Self>(dh);
+        ContentTypeState::new::<Self>(dh);
         SinglePixelBufferState::new::<Self>(dh);
         FixesState::new::<Self>(&dh);
 
@@ -738,7 +758,12 @@ impl State {
 
         let a11y_keyboard_monitor_state = A11yKeyboardMonitorState::new(&async_executor);
 
-        let night_light = std::sync::Arc::new(parking_lot::Mutex::new(crate::dbus::night_light::NightLightState::new()));
+        let night_light = std::sync::Arc::new(parking_lot::Mutex::new(
+            crate::night_light::NightLightState::new(config.dynamic_conf.night_light()),
+        ));
+        if let Err(err) = crate::night_light::schedule::init(&handle) {
+            tracing::warn!(?err, "Failed to start night light schedule");
+        }
 
         State {
             common: Common {
@@ -767,6 +792,7 @@ impl State {
                 data_device_state,
                 dmabuf_state,
                 fractional_scale_state,
+                gamma_control_state,
                 idle_notifier_state,
                 idle_inhibit_manager_state,
                 idle_inhibiting_surfaces,
@@ -809,6 +835,8 @@ impl State {
                 inhibit_lid_fd: None,
 
                 night_light,
+                night_light_signals: None,
+                night_light_override_timer: None,
             },
             backend: BackendData::Unset,
             ready: Once::new(),
@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use anyhow::Context;
 use smithay::{
     backend::{
@@ -10,13 +12,15 @@ use smithay::{
         },
     },
     desktop::utils::bbox_from_surface_tree,
-    utils::{Scale, Transform},
+    utils::{Rectangle, Scale, Transform},
     wayland::seat::WaylandFocus,
 };
 use tracing::warn;
 
 use crate::{
-    backend::render::RendererRef,
+    backend::render::{RendererRef, postprocess},
+    config::ScreenFilter,
+    night_light::{Adaptation, OutputKey, capture::CaptureSource},
     shell::element::CosmicSurface,
     state::{State, advertised_node_for_surface},
 };
@@ -26,6 +30,7 @@ pub fn screenshot_window(state: &mut State, surface: &CosmicSurface) {
         renderer: &mut R,
         window: &CosmicSurface,
         offset: &time::UtcOffset,
+        tint: Option<Adaptation>,
     ) -> anyhow::Result<()>
     where
         R: Renderer + ImportAll + Offscreen<GlesRenderbuffer> + ExportMem,
@@ -62,7 +67,19 @@ pub fn screenshot_window(state: &mut State, surface: &CosmicSurface) {
             bbox.to_buffer(1, Transform::Normal, &bbox.size),
             format,
         )?;
-        let gl_data = renderer.map_texture(&mapping)?;
+        let mut gl_data = Cow::Borrowed(renderer.map_texture(&mapping)?);
+        if let Some(night_light) = tint {
+            let size = bbox.size.to_buffer(1, Transform::Normal);
+            postprocess::postprocess_pixels(
+                gl_data.to_mut(),
+                format,
+                size,
+                size.w as usize * 4,
+                &[Rectangle::from_size(size)],
+                &ScreenFilter::default(),
+                night_light,
+            )?;
+        }
 
         if let Ok(Some(path)) = xdg_user::pictures() {
             let local_timestamp = time::OffsetDateTime::now_utc().to_offset(*offset);
@@ -93,13 +110,30 @@ pub fn screenshot_window(state: &mut State, surface: &CosmicSurface) {
             );
             encoder.set_source_chromaticities(source_chromaticities);
             let mut writer = encoder.write_header()?;
-            writer.write_image_data(gl_data)?;
+            writer.write_image_data(&gl_data)?;
         }
 
         Ok(())
     }
 
     if let Some(wl_surface) = surface.wl_surface() {
+        let output = {
+            let shell = state.common.shell.read();
+            shell
+                .element_for_surface(surface)
+                .and_then(|element| shell.space_for(element))
+                .map(|workspace| workspace.output().clone())
+                // exempt windows are captured untinted, like they are shown
+                .filter(|_| !shell.is_night_light_exempt(surface))
+        };
+        let tint = output.and_then(|output| {
+            state
+                .common
+                .night_light
+                .lock()
+                .capture_adaptation(CaptureSource::Screenshot, &OutputKey::from(&output))
+        });
+
         let res = state
             .backend
             .offscreen_renderer(|kms| {
@@ -109,10 +143,10 @@ pub fn screenshot_window(state: &mut State, surface: &CosmicSurface) {
             .with_context(|| "Failed to get renderer for screenshot")
             .and_then(|renderer| match renderer {
                 RendererRef::Glow(renderer) => {
-                    render_window(renderer, surface, &state.common.local_offset)
+                    render_window(renderer, surface, &state.common.local_offset, tint)
                 }
                 RendererRef::GlMulti(mut renderer) => {
-                    render_window(&mut renderer, surface, &state.common.local_offset)
+                    render_window(&mut renderer, surface, &state.common.local_offset, tint)
                 }
             });
         if let Err(err) = res {
@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use smithay::output::Output;
+use tracing::warn;
+
+use crate::{
+    backend::kms::{GammaRamp, Surface},
+    night_light::OutputKey,
+    state::{BackendData, State},
+    wayland::protocols::gamma_control::{
+        GammaControlHandler, GammaControlState, delegate_gamma_control,
+    },
+};
+
+// Gamma ramps only exist on KMS, other backends don't offer any to clients
+fn kms_surface_for_output<'a>(state: &'a mut State, output: &Output) -> Option<&'a mut Surface> {
+    let BackendData::Kms(kms_state) = &mut state.backend else {
+        return None;
+    };
+    kms_state
+        .drm_devices
+        .values_mut()
+        .flat_map(|device| device.inner.surfaces.values_mut())
+        .find(|surface| surface.output == *output)
+}
+
+impl GammaControlHandler for State {
+    fn gamma_control_state(&mut self) -> &mut GammaControlState {
+        &mut self.common.gamma_control_state
+    }
+
+    fn gamma_size(&mut self, output: &Output) -> Option<u32> {
+        let surface = kms_surface_for_output(self, output)?;
+        surface.gamma_size().ok()
+    }
+
+    fn set_gamma(&mut self, output: &Output, ramp: Option<Vec<u16>>) -> bool {
+        let Some(surface) = kms_surface_for_output(self, output) else {
+            return false;
+        };
+        let ramp = ramp.map(|values| GammaRamp::from_planar(&values));
+        let active = ramp.is_some();
+        match surface.set_gamma(ramp) {
+            Ok(()) => {
+                self.common
+                    .night_light
+                    .lock()
+                    .set_gamma_client(OutputKey::from(output), active);
+                true
+            }
+            Err(err) => {
+                warn!(
+                    ?err,
+                    output = output.name(),
+                    "Failed to set client gamma ramp"
+                );
+                false
+            }
+        }
+    }
+}
+
+delegate_gamma_control!(State);
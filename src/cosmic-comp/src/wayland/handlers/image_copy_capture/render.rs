@@ -13,7 +13,7 @@ use smithay::{
                 surface::WaylandSurfaceRenderElement,
                 utils::{Relocate, RelocateRenderElement},
             },
-            gles::{GlesError, GlesRenderbuffer},
+            gles::{GlesError, GlesRenderbuffer, GlesTexture},
             sync::SyncPoint,
             utils::with_renderer_surface_state,
         },
@@ -42,7 +42,11 @@ use crate::{
     backend::render::{
         CursorMode, ElementFilter, RendererRef, cursor,
         element::{AsGlowRenderer, CosmicElement, DamageElement, FromGlesError},
-        render_workspace,
+        render_workspace, tint_framebuffer,
+    },
+    night_light::{
+        OutputKey,
+        capture::{CaptureSource, CaptureTint},
     },
     shell::{CosmicMappedRenderElement, CosmicSurface, WorkspaceRenderElement},
     state::{Common, KmsNodes, State},
@@ -185,15 +189,64 @@ where
     }))
 }
 
+/// Applies the night light to the freshly rendered `damage` of a capture buffer.
+///
+/// Returns `None` if there was nothing to tint.
+fn tint_buffer<R>(
+    renderer: &mut R,
+    buffer: &WlBuffer,
+    offscreen: Option<&mut R::Framebuffer<'_>>,
+    transform: Transform,
+    damage: &[Rectangle<i32, Physical>],
+    tint: &CaptureTint,
+) -> Result<Option<SyncPoint>, R::Error>
+where
+    R: Offscreen<GlesTexture> + Bind<Dmabuf> + Blit + AsGlowRenderer,
+    R::Error: FromGlesError,
+{
+    if damage.is_empty() {
+        return Ok(None);
+    }
+    let buffer_size = buffer_dimensions(buffer).unwrap();
+    // damage is in output space, the tint is applied to the untransformed buffer
+    let to_buffer = |rects: &[Rectangle<i32, Physical>]| {
+        rects
+            .iter()
+            .map(|rect| {
+                rect.to_logical(1)
+                    .to_buffer(1, transform.invert(), &buffer_size.to_logical(1, transform))
+                    .to_logical(1, Transform::Normal, &buffer_size)
+                    .to_physical(1)
+            })
+            .collect::<Vec<_>>()
+    };
+    let (damage, exempt) = (to_buffer(damage), to_buffer(&tint.exempt));
+    let size = buffer_size.to_logical(1, Transform::Normal).to_physical(1);
+
+    let sync = match offscreen {
+        Some(fb) => tint_framebuffer(renderer, fb, size, &damage, &exempt, tint.adaptation)?,
+        None => {
+            let Ok(dmabuf) = get_dmabuf(buffer) else {
+                return Ok(None);
+            };
+            let mut dmabuf = dmabuf.clone();
+            let mut fb = renderer.bind(&mut dmabuf)?;
+            tint_framebuffer(renderer, &mut fb, size, &damage, &exempt, tint.adaptation)?
+        }
+    };
+    Ok(Some(sync))
+}
+
 pub fn render_session<F, R, T>(
     renderer: &mut R,
     session: &SessionData,
     frame: Frame,
     transform: Transform,
+    tint: Option<CaptureTint>,
     render_fn: F,
 ) -> Result<Option<PendingImageCopyData>, DTError<R::Error>>
 where
-    R: ExportMem + Offscreen<T>,
+    R: ExportMem + Offscreen<T> + Offscreen<GlesTexture> + Bind<Dmabuf> + Blit + AsGlowRenderer,
     R::Error: FromGlesError,
     F: for<'d> FnOnce(
         &WlBuffer,
@@ -215,15 +268,17 @@ where
                     .expect("We should be able to convert all hardcoded shm screencopy formats")
             })
             .map_err(|_| DTError::OutputNoMode(OutputNoMode))?;
-            renderer
-                .create_buffer(format, size)
-                .map_err(DTError::Rendering)
+            Offscreen::<T>::create_buffer(renderer, format, size).map_err(DTError::Rendering)
         })
         .transpose()?;
 
     let age = if offscreen.is_some() {
         // TODO re-use offscreen buffer to damage track screencopy to shm
         0
+    } else if session_damage_tracking.tint != tint {
+        // the undamaged parts of the buffer still carry the old tint
+        session_damage_tracking.tint = tint.clone();
+        0
     } else {
         1
     };
@@ -241,15 +296,27 @@ where
     );
 
     match res {
-        Ok(result) => submit_buffer(
-            frame,
-            renderer,
-            fb.as_mut(),
-            transform,
-            result.damage.map(|x| x.as_slice()),
-            result.sync,
-        )
-        .map_err(DTError::Rendering),
+        Ok(mut result) => {
+            if let (Some(tint), Some(damage)) = (tint.as_ref(), result.damage) {
+                match tint_buffer(renderer, &buffer, fb.as_mut(), transform, damage, tint) {
+                    Ok(Some(sync)) => result.sync = sync,
+                    Ok(None) => {}
+                    Err(err) => {
+                        frame.fail(CaptureFailureReason::Unknown);
+                        return Err(DTError::Rendering(err));
+                    }
+                }
+            }
+            submit_buffer(
+                frame,
+                renderer,
+                fb.as_mut(),
+                transform,
+                result.damage.map(|x| x.as_slice()),
+                result.sync,
+            )
+            .map_err(DTError::Rendering)
+        }
         Err(err) => {
             frame.fail(CaptureFailureReason::Unknown);
             Err(err)
@@ -390,6 +457,24 @@ pub fn render_workspace_to_buffer(
     let draw_cursor = session.draw_cursor();
     let transform = output.current_transform();
     let common = &mut state.common;
+    let adaptation = common
+        .night_light
+        .lock()
+        .capture_adaptation(CaptureSource::Workspace, &OutputKey::from(&output));
+    let tint = adaptation.map(|adaptation| {
+        let shell = common.shell.read();
+        // exempt windows are only tracked for the workspace shown on the output
+        let exempt = if shell.active_space(&output).map(|w| w.handle) == Some(handle) {
+            shell.night_light_exempt_regions(&output)
+        } else {
+            Vec::new()
+        };
+        CaptureTint::new(
+            adaptation,
+            &exempt,
+            output.current_scale().fractional_scale(),
+        )
+    });
 
     let renderer = match state.backend.offscreen_renderer(|kms| {
         let render_node = kms
@@ -429,6 +514,7 @@ pub fn render_workspace_to_buffer(
                 session.user_data().get::<SessionData>().unwrap(),
                 frame,
                 transform,
+                tint,
                 |buffer, renderer, offscreen, dt, age, additional_damage| {
                     render_fn(
                         buffer,
@@ -457,6 +543,7 @@ pub fn render_workspace_to_buffer(
                 session.user_data().get::<SessionData>().unwrap(),
                 frame,
                 transform,
+                tint,
                 |buffer, renderer, offscreen, dt, age, additional_damage| {
                     render_fn(
                         buffer,
@@ -646,6 +733,25 @@ pub fn render_window_to_buffer(
 
     let common = &mut state.common;
     let draw_cursor = session.draw_cursor();
+    let output = {
+        let shell = common.shell.read();
+        shell
+            .element_for_surface(toplevel)
+            .and_then(|element| shell.space_for(element))
+            .map(|workspace| workspace.output().clone())
+            // exempt windows are captured untinted, like they are shown
+            .filter(|_| !shell.is_night_light_exempt(toplevel))
+    };
+    let tint = output.and_then(|output| {
+        let adaptation = common
+            .night_light
+            .lock()
+            .capture_adaptation(CaptureSource::Toplevel, &OutputKey::from(&output))?;
+        Some(CaptureTint {
+            adaptation,
+            exempt: Vec::new(),
+        })
+    });
 
     let renderer = match state.backend.offscreen_renderer(|kms| {
         get_dmabuf(&buffer)
@@ -678,6 +784,7 @@ pub fn render_window_to_buffer(
             session.user_data().get::<SessionData>().unwrap(),
             frame,
             Transform::Normal,
+            tint,
             |buffer, renderer, offscreen, dt, age, additional_damage| {
                 render_fn(
                     buffer,
@@ -704,6 +811,7 @@ pub fn render_window_to_buffer(
             session.user_data().get::<SessionData>().unwrap(),
             frame,
             Transform::Normal,
+            tint,
             |buffer, renderer, offscreen, dt, age, additional_damage| {
                 render_fn(
                     buffer,
@@ -820,6 +928,18 @@ pub fn render_cursor_to_buffer(
     }
 
     let common = &mut state.common;
+    // cursor sessions are drawn on top of output captures, so they have to match those
+    let tint = common
+        .night_light
+        .lock()
+        .capture_adaptation(
+            CaptureSource::Output,
+            &OutputKey::from(&seat.active_output()),
+        )
+        .map(|adaptation| CaptureTint {
+            adaptation,
+            exempt: Vec::new(),
+        });
     let renderer = match state
         .backend
         .offscreen_renderer(|kms| *kms.primary_node.read().unwrap())
@@ -838,6 +958,7 @@ pub fn render_cursor_to_buffer(
                 session.user_data().get::<SessionData>().unwrap(),
                 frame,
                 Transform::Normal,
+                tint,
                 |buffer, renderer, offscreen, dt, age, additional_damage| {
                     render_fn(
                         buffer,
@@ -864,6 +985,7 @@ pub fn render_cursor_to_buffer(
                 session.user_data().get::<SessionData>().unwrap(),
                 frame,
                 Transform::Normal,
+                tint,
                 |buffer, renderer, offscreen, dt, age, additional_damage| {
                     render_fn(
                         buffer,
@@ -10,7 +10,10 @@ use smithay::{
     },
 };
 
-use crate::shell::{CosmicSurface, Workspace};
+use crate::{
+    night_light::capture::CaptureTint,
+    shell::{CosmicSurface, Workspace},
+};
 
 type ImageCopySessionsData = RefCell<ImageCopySessions>;
 type PendingImageCopyBuffers = Mutex<Vec<(SessionRef, Frame)>>;
@@ -19,11 +22,16 @@ pub type SessionData = Mutex<SessionUserData>;
 
 pub struct SessionUserData {
     pub dt: OutputDamageTracker,
+    /// Night light tint the session's buffers were last rendered with
+    pub tint: Option<CaptureTint>,
 }
 
 impl SessionUserData {
     pub fn new(tracker: OutputDamageTracker) -> SessionUserData {
-        SessionUserData { dt: tracker }
+        SessionUserData {
+            dt: tracker,
+            tint: None,
+        }
     }
 }
 
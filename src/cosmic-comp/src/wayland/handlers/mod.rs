@@ -4,6 +4,7 @@ pub mod a11y;
 pub mod alpha_modifier;
 pub mod buffer;
 pub mod compositor;
+pub mod content_type;
 pub mod corner_radius;
 pub mod data_control;
 pub mod data_device;
@@ -15,6 +16,7 @@ pub mod drm_syncobj;
 pub mod fixes;
 pub mod foreign_toplevel_list;
 pub mod fractional_scale;
+pub mod gamma_control;
 pub mod idle_inhibit;
 pub mod idle_notify;
 pub mod image_capture_source;
@@ -0,0 +1,262 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! `wlr-gamma-control-unstable-v1`, used by gammastep, wlsunset and calibration tools.
+//!
+//! Only one client can control the gamma ramp of an output at a time.
+//! While it does, the built-in night light is suspended for that output,
+//! so the client's ramp is shown unaltered. Destroying the control restores the night light.
+
+use smithay::{
+    output::{Output, WeakOutput},
+    reexports::{
+        wayland_protocols_wlr::gamma_control::v1::server::{
+            zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+            zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+        },
+        wayland_server::{
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+            backend::GlobalId,
+        },
+    },
+};
+use std::{
+    fs::File,
+    os::unix::fs::FileExt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tracing::debug;
+use wayland_backend::server::ClientId;
+
+pub trait GammaControlHandler {
+    fn gamma_control_state(&mut self) -> &mut GammaControlState;
+    /// Number of entries per channel of the output's gamma ramp, `None` if it has none.
+    fn gamma_size(&mut self, output: &Output) -> Option<u32>;
+    /// Sets the ramp of the output, laid out as all red, then all green, then all blue values.
+    ///
+    /// `None` hands the output back to the compositor. Returns `false` if the ramp was rejected.
+    fn set_gamma(&mut self, output: &Output, ramp: Option<Vec<u16>>) -> bool;
+}
+
+#[derive(Debug)]
+pub struct GammaControlState {
+    global: GlobalId,
+    /// Controls that haven't failed yet, at most one per output
+    controls: Vec<ZwlrGammaControlV1>,
+}
+
+impl GammaControlState {
+    pub fn new<D, F>(dh: &DisplayHandle, client_filter: F) -> GammaControlState
+    where
+        D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData> + 'static,
+        F: for<'a> Fn(&'a Client) -> bool + Clone + Send + Sync + 'static,
+    {
+        let global = dh.create_global::<D, ZwlrGammaControlManagerV1, _>(
+            1,
+            GammaControlManagerGlobalData {
+                filter: Box::new(client_filter.clone()),
+            },
+        );
+
+        GammaControlState {
+            global,
+            controls: Vec::new(),
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Sends `failed` to the controls of a disconnected output.
+    pub fn output_removed(&mut self, output: &Output) {
+        self.controls.retain(|control| {
+            let data = control.data::<GammaControlData>().unwrap();
+            let removed = data.output == *output;
+            if removed {
+                // the ramp went away with the crtc
+                data.applied.store(false, Ordering::SeqCst);
+                control.failed();
+            }
+            !removed
+        });
+    }
+}
+
+pub struct GammaControlManagerGlobalData {
+    filter: Box<dyn for<'a> Fn(&'a Client) -> bool + Send + Sync>,
+}
+
+pub struct GammaControlData {
+    output: WeakOutput,
+    size: u32,
+    /// Whether the client's ramp is currently applied
+    applied: AtomicBool,
+}
+
+impl<D> GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData, D>
+    for GammaControlState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrGammaControlManagerV1>,
+        _global_data: &GammaControlManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GammaControlManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, GammaControlData>
+        + GammaControlHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZwlrGammaControlManagerV1,
+        request: zwlr_gamma_control_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_manager_v1::Request::GetGammaControl { id, output } => {
+                let output = Output::from_resource(&output);
+                let taken = output.as_ref().is_some_and(|output| {
+                    state.gamma_control_state().controls.iter().any(|control| {
+                        control.data::<GammaControlData>().unwrap().output == *output
+                    })
+                });
+                let size = output
+                    .as_ref()
+                    .filter(|_| !taken)
+                    .and_then(|output| state.gamma_size(output));
+
+                let control = data_init.init(
+                    id,
+                    GammaControlData {
+                        output: output.as_ref().map(|o| o.downgrade()).unwrap_or_default(),
+                        size: size.unwrap_or(0),
+                        applied: AtomicBool::new(false),
+                    },
+                );
+                match size {
+                    Some(size) => {
+                        control.gamma_size(size);
+                        state.gamma_control_state().controls.push(control);
+                    }
+                    None => control.failed(),
+                }
+            }
+            zwlr_gamma_control_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlV1, GammaControlData, D> for GammaControlState
+where
+    D: Dispatch<ZwlrGammaControlV1, GammaControlData> + GammaControlHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &ZwlrGammaControlV1,
+        request: zwlr_gamma_control_v1::Request,
+        data: &GammaControlData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_v1::Request::SetGamma { fd } => {
+                if !state.gamma_control_state().controls.contains(obj) {
+                    // already failed
+                    return;
+                }
+                let Some(output) = data.output.upgrade() else {
+                    fail(state, obj, data);
+                    return;
+                };
+
+                let mut bytes = vec![0u8; data.size as usize * 3 * 2];
+                if let Err(err) = File::from(fd).read_exact_at(&mut bytes, 0) {
+                    debug!(?err, "Failed to read gamma ramp");
+                    fail(state, obj, data);
+                    return;
+                }
+                let ramp = bytes
+                    .chunks_exact(2)
+                    .map(|value| u16::from_ne_bytes([value[0], value[1]]))
+                    .collect();
+
+                if state.set_gamma(&output, Some(ramp)) {
+                    data.applied.store(true, Ordering::SeqCst);
+                } else {
+                    fail(state, obj, data);
+                }
+            }
+            zwlr_gamma_control_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        obj: &ZwlrGammaControlV1,
+        data: &GammaControlData,
+    ) {
+        state
+            .gamma_control_state()
+            .controls
+            .retain(|control| control != obj);
+        reset(state, data);
+    }
+}
+
+fn fail<D: GammaControlHandler>(state: &mut D, obj: &ZwlrGammaControlV1, data: &GammaControlData) {
+    obj.failed();
+    state
+        .gamma_control_state()
+        .controls
+        .retain(|control| control != obj);
+    reset(state, data);
+}
+
+/// Restores the gamma ramp, if the client changed it.
+fn reset<D: GammaControlHandler>(state: &mut D, data: &GammaControlData) {
+    if data.applied.swap(false, Ordering::SeqCst) {
+        if let Some(output) = data.output.upgrade() {
+            state.set_gamma(&output, None);
+        }
+    }
+}
+
+macro_rules! delegate_gamma_control {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: $crate::wayland::protocols::gamma_control::GammaControlManagerGlobalData
+        ] => $crate::wayland::protocols::gamma_control::GammaControlState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::wayland::protocols::gamma_control::GammaControlState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_v1::ZwlrGammaControlV1: $crate::wayland::protocols::gamma_control::GammaControlData
+        ] => $crate::wayland::protocols::gamma_control::GammaControlState);
+    };
+}
+pub(crate) use delegate_gamma_control;
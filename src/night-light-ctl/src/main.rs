@@ -0,0 +1,482 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Terminal control for Elite Night Light, talking to the compositor over D-Bus.
+
+use std::{
+    collections::HashMap,
+    process::{Command, ExitCode},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use night_light_proxy::{NightLightProxyBlocking, INTERFACE, PATH, SERVICE};
+use serde::Serialize;
+use zbus::{
+    blocking::{fdo::PropertiesProxy, Connection},
+    proxy::CacheProperties,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Control Elite Night Light")]
+struct Cli {
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Show the current state
+    Status {
+        /// Print a single JSON object instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Turn night light on
+    On,
+    /// Turn night light off
+    Off,
+    /// Turn night light on if it is off, and off otherwise
+    Toggle,
+    /// Change the intensity, which also turns night light on
+    Set(SetArgs),
+    /// Lower the temperature by one step and turn night light on
+    Warmer,
+    /// Raise the temperature by one step and turn night light on
+    Cooler,
+    /// Switch to the next intensity level and turn night light on
+    Cycle,
+    /// Show or change the schedule
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCmd,
+    },
+    /// Keep night light off while a command runs, e.g. `inhibit -- displaycal`
+    Inhibit {
+        /// Why night light is off, shown to other clients
+        #[arg(long, default_value = "night-light-ctl")]
+        reason: String,
+        #[arg(required = true, last = true)]
+        command: Vec<String>,
+    },
+    /// Print the state as a JSON line now and whenever it changes
+    Watch,
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct SetArgs {
+    /// Color temperature in Kelvin, 1000 to 6500
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1000..=6500))]
+    temperature: Option<u32>,
+    /// Preset intensity, 1 (soft) to 3 (strong)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=3))]
+    level: Option<u8>,
+}
+
+#[derive(Subcommand)]
+enum ScheduleCmd {
+    /// Show the schedule
+    Show {
+        /// Print a single JSON object instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Change the given parts of the schedule
+    Set(ScheduleArgs),
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = true)]
+struct ScheduleArgs {
+    #[arg(long, value_parser = ["disabled", "custom", "solar"])]
+    mode: Option<String>,
+    /// Start of the custom schedule, as HH:MM
+    #[arg(long, value_parser = parse_time)]
+    from: Option<u16>,
+    /// End of the custom schedule, as HH:MM
+    #[arg(long, value_parser = parse_time)]
+    to: Option<u16>,
+    /// Minutes night light fades in and out after each transition
+    #[arg(long)]
+    ramp: Option<u16>,
+    /// Position for the solar schedule, as LATITUDE,LONGITUDE in degrees
+    #[arg(long, value_parser = parse_location, allow_hyphen_values = true)]
+    location: Option<(f64, f64)>,
+}
+
+#[derive(Debug, Serialize)]
+struct Status {
+    enabled: bool,
+    level: u8,
+    temperature: u32,
+    dim: f64,
+    inhibited: bool,
+    #[serde(rename = "override")]
+    timed_override: Option<Override>,
+    /// Paused outputs, with the content that paused them
+    paused: HashMap<String, String>,
+    schedule: Schedule,
+}
+
+#[derive(Debug, Serialize)]
+struct Override {
+    enabled: bool,
+    /// Unix timestamp, `None` if it lasts until the next schedule transition
+    until: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Schedule {
+    mode: String,
+    from: String,
+    to: String,
+    ramp: u16,
+    location: Option<(f64, f64)>,
+}
+
+impl Status {
+    fn fetch(proxy: &NightLightProxyBlocking<'_>) -> Result<Status> {
+        let timed_override = match proxy.override_()?.as_str() {
+            "" => None,
+            state => Some(Override {
+                enabled: state == "enabled",
+                until: Some(proxy.override_expiry()?).filter(|until| *until != 0),
+            }),
+        };
+        Ok(Status {
+            enabled: proxy.enabled()?,
+            level: proxy.level()?,
+            temperature: proxy.temperature()?,
+            dim: proxy.dim()?,
+            inhibited: proxy.inhibited()?,
+            timed_override,
+            paused: proxy.suspend_reasons()?,
+            schedule: Schedule::fetch(proxy)?,
+        })
+    }
+
+    fn print(&self) {
+        let level = match self.level {
+            0 => String::new(),
+            level => format!(", level {level}"),
+        };
+        println!("Night light: {}", on_off(self.enabled));
+        println!("Temperature: {}K{level}", self.temperature);
+        if self.dim > 0. {
+            println!("Dim: {:.0}%", self.dim * 100.);
+        }
+        if let Some(timed_override) = &self.timed_override {
+            let until = match timed_override.until {
+                Some(until) => format!("for {} more minutes", minutes_until(until)),
+                None => "until the next transition".into(),
+            };
+            println!("Override: {} {until}", on_off(timed_override.enabled));
+        }
+        if self.inhibited {
+            println!("Inhibited: yes");
+        }
+        let mut paused = self.paused.iter().collect::<Vec<_>>();
+        paused.sort();
+        for (output, content) in paused {
+            println!("Paused: {output} ({content})");
+        }
+        self.schedule.print();
+    }
+}
+
+impl Schedule {
+    fn fetch(proxy: &NightLightProxyBlocking<'_>) -> Result<Schedule> {
+        Ok(Schedule {
+            mode: proxy.schedule_mode()?,
+            from: format_time(proxy.schedule_from()?),
+            to: format_time(proxy.schedule_to()?),
+            ramp: proxy.schedule_ramp()?,
+            // an error until a location is set
+            location: proxy.location().ok(),
+        })
+    }
+
+    fn print(&self) {
+        match self.mode.as_str() {
+            "custom" => println!("Schedule: {} - {}", self.from, self.to),
+            "solar" => match self.location {
+                Some((latitude, longitude)) => {
+                    println!("Schedule: dusk to dawn at {latitude}, {longitude}")
+                }
+                None => println!("Schedule: dusk to dawn, no location set"),
+            },
+            _ => println!("Schedule: off"),
+        }
+        if self.mode != "disabled" && self.ramp > 0 {
+            println!("Ramp: {} minutes", self.ramp);
+        }
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn format_time(minute: u16) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+fn parse_time(value: &str) -> Result<u16, String> {
+    // `parse` alone would take signs, and "7:5" is too easily meant as 07:50
+    let digits = |part: &str, len: std::ops::RangeInclusive<usize>| {
+        (len.contains(&part.len()) && part.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| part.parse::<u16>().ok())
+            .flatten()
+    };
+    let (hour, minute) = value
+        .split_once(':')
+        .and_then(|(hour, minute)| Some((digits(hour, 1..=2)?, digits(minute, 2..=2)?)))
+        .filter(|(hour, minute)| *hour < 24 && *minute < 60)
+        .ok_or_else(|| format!("`{value}` is not a time of day like 19:30"))?;
+    Ok(hour * 60 + minute)
+}
+
+fn parse_location(value: &str) -> Result<(f64, f64), String> {
+    value
+        .split_once(',')
+        .and_then(|(latitude, longitude)| {
+            Some((
+                latitude.trim().parse().ok()?,
+                longitude.trim().parse().ok()?,
+            ))
+        })
+        .ok_or_else(|| format!("`{value}` is not a location like 52.52,13.40"))
+}
+
+fn minutes_until(timestamp: i64) -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as i64);
+    // round up, so the last minute doesn't show as zero
+    (timestamp - now + 59).max(0) / 60
+}
+
+fn connect() -> Result<(Connection, NightLightProxyBlocking<'static>)> {
+    let conn = Connection::session().context("Failed to connect to the session bus")?;
+    // always ask the compositor, cached values may lag behind our own changes
+    let proxy = NightLightProxyBlocking::builder(&conn)
+        .cache_properties(CacheProperties::No)
+        .build()?;
+    // fail early with a useful message, instead of on the first property
+    proxy
+        .enabled()
+        .context("Night light is not available, is the patched cosmic-comp running?")?;
+    Ok((conn, proxy))
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let (conn, proxy) = connect()?;
+
+    match cli.command {
+        Cmd::Status { json } => {
+            let status = Status::fetch(&proxy)?;
+            if json {
+                println!("{}", serde_json::to_string(&status)?);
+            } else {
+                status.print();
+            }
+        }
+        Cmd::On => proxy.set_enabled(true)?,
+        Cmd::Off => proxy.set_enabled(false)?,
+        Cmd::Toggle => {
+            let enabled = !proxy.enabled()?;
+            proxy.set_enabled(enabled)?;
+            println!("Night light: {}", on_off(enabled));
+        }
+        Cmd::Set(args) => {
+            if let Some(level) = args.level {
+                proxy.set_level(level)?;
+            }
+            if let Some(kelvin) = args.temperature {
+                proxy.set_temperature(kelvin)?;
+            }
+            proxy.set_enabled(true)?;
+        }
+        // done by the compositor, so quick repeats don't race each other
+        Cmd::Warmer | Cmd::Cooler => {
+            let kelvin = proxy.step_temperature(matches!(cli.command, Cmd::Warmer))?;
+            println!("Temperature: {kelvin}K");
+        }
+        Cmd::Cycle => {
+            let level = proxy.cycle_level()?;
+            println!("Level: {level}");
+        }
+        Cmd::Schedule {
+            command: ScheduleCmd::Show { json },
+        } => {
+            let schedule = Schedule::fetch(&proxy)?;
+            if json {
+                println!("{}", serde_json::to_string(&schedule)?);
+            } else {
+                schedule.print();
+            }
+        }
+        Cmd::Schedule {
+            command: ScheduleCmd::Set(args),
+        } => {
+            // set the times first, so switching the mode applies the new ones right away
+            if let Some(minute) = args.from {
+                proxy.set_schedule_from(minute)?;
+            }
+            if let Some(minute) = args.to {
+                proxy.set_schedule_to(minute)?;
+            }
+            if let Some(minutes) = args.ramp {
+                proxy.set_schedule_ramp(minutes)?;
+            }
+            if let Some(location) = args.location {
+                proxy.set_location(location)?;
+            }
+            if let Some(mode) = args.mode {
+                proxy.set_schedule_mode(&mode)?;
+            }
+        }
+        Cmd::Inhibit { reason, command } => {
+            // ends by itself if we get killed, as the compositor watches our connection
+            let cookie = proxy.inhibit(&reason)?;
+            let status = Command::new(&command[0])
+                .args(&command[1..])
+                .status()
+                .with_context(|| format!("Failed to run `{}`", command[0]));
+            proxy.uninhibit(cookie)?;
+            return Ok(match status?.code() {
+                Some(code) => ExitCode::from(code as u8),
+                // killed by a signal
+                None => ExitCode::FAILURE,
+            });
+        }
+        Cmd::Watch => {
+            let properties = PropertiesProxy::builder(&conn)
+                .destination(SERVICE)?
+                .path(PATH)?
+                .build()?;
+            let changes = properties.receive_properties_changed()?;
+            println!("{}", serde_json::to_string(&Status::fetch(&proxy)?)?);
+            for change in changes {
+                if change.args()?.interface_name().as_str() != INTERFACE {
+                    continue;
+                }
+                println!("{}", serde_json::to_string(&Status::fetch(&proxy)?)?);
+            }
+            bail!("Lost the connection to the session bus");
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+    use serde_json::Value;
+
+    use super::{parse_location, parse_time, Cli, Cmd, Override, Schedule, ScheduleCmd, Status};
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("00:00"), Ok(0));
+        assert_eq!(parse_time("7:05"), Ok(7 * 60 + 5));
+        assert_eq!(parse_time("23:59"), Ok(23 * 60 + 59));
+        for value in [
+            "24:00", "7:5", "-1:00", "+7:00", "12:60", "12", "12:00:00", ":30", "",
+        ] {
+            assert!(parse_time(value).is_err(), "{value:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn test_parse_location() {
+        assert_eq!(parse_location("52.52,13.40"), Ok((52.52, 13.40)));
+        assert_eq!(parse_location("-33.92, -18.42"), Ok((-33.92, -18.42)));
+        for value in ["52.52", "north,east", ""] {
+            assert!(parse_location(value).is_err(), "{value:?} was accepted");
+        }
+
+        // negative coordinates must not be mistaken for flags
+        let cli = Cli::try_parse_from([
+            "night-light-ctl",
+            "schedule",
+            "set",
+            "--location",
+            "-33.92,-18.42",
+        ])
+        .unwrap();
+        let Cmd::Schedule {
+            command: ScheduleCmd::Set(args),
+        } = cli.command
+        else {
+            panic!("parsed as another command");
+        };
+        assert_eq!(args.location, Some((-33.92, -18.42)));
+    }
+
+    #[test]
+    fn test_json_fields() {
+        let status = Status {
+            enabled: true,
+            level: 2,
+            temperature: 3500,
+            dim: 0.,
+            inhibited: false,
+            timed_override: Some(Override {
+                enabled: false,
+                until: None,
+            }),
+            paused: [("DP-1".into(), "video".into())].into(),
+            schedule: Schedule {
+                mode: "custom".into(),
+                from: "19:00".into(),
+                to: "07:00".into(),
+                ramp: 30,
+                location: Some((52.52, 13.4)),
+            },
+        };
+        let json = serde_json::to_value(&status).unwrap();
+
+        // scripts depend on these, so renaming any of them is a breaking change
+        let keys = |value: &Value| {
+            let mut keys = value
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            keys.sort();
+            keys
+        };
+        assert_eq!(
+            keys(&json),
+            [
+                "dim",
+                "enabled",
+                "inhibited",
+                "level",
+                "override",
+                "paused",
+                "schedule",
+                "temperature"
+            ]
+        );
+        assert_eq!(keys(&json["override"]), ["enabled", "until"]);
+        assert_eq!(
+            keys(&json["schedule"]),
+            ["from", "location", "mode", "ramp", "to"]
+        );
+        assert_eq!(json["paused"]["DP-1"], "video");
+        assert_eq!(
+            json["schedule"]["location"],
+            serde_json::json!([52.52, 13.4])
+        );
+    }
+}
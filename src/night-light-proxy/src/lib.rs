@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Client side of the `io.github.kernel_ux.EliteNightLight.Manager` interface,
+//! shared by the panel applet and `night-light-ctl`.
+
+pub const SERVICE: &str = "io.github.kernel_ux.EliteNightLight";
+pub const PATH: &str = "/io/github/kernel_ux/EliteNightLight";
+pub const INTERFACE: &str = "io.github.kernel_ux.EliteNightLight.Manager";
+
+/// Connected output as returned by `GetOutputs`: connector, make and model, enabled,
+/// temperature and whether any of those are overridden for the output.
+pub type OutputInfo = (String, String, bool, u32, bool);
+
+#[zbus::proxy(
+    interface = "io.github.kernel_ux.EliteNightLight.Manager",
+    default_service = "io.github.kernel_ux.EliteNightLight",
+    default_path = "/io/github/kernel_ux/EliteNightLight"
+)]
+pub trait NightLight {
+    #[zbus(property, name = "Enabled")]
+    fn enabled(&self) -> zbus::Result<bool>;
+    #[zbus(property, name = "Enabled")]
+    fn set_enabled(&self, enabled: bool) -> zbus::Result<()>;
+
+    /// Preset intensity 1 to 3, or `0` for a custom temperature.
+    #[zbus(property, name = "Level")]
+    fn level(&self) -> zbus::Result<u8>;
+    #[zbus(property, name = "Level")]
+    fn set_level(&self, level: u8) -> zbus::Result<()>;
+
+    /// Temperatures of the levels 1 to 3 in Kelvin.
+    #[zbus(property(emits_changed_signal = "const"), name = "LevelTemperatures")]
+    fn level_temperatures(&self) -> zbus::Result<Vec<u32>>;
+
+    /// Color temperature in Kelvin, 1000 to 6500.
+    #[zbus(property, name = "Temperature")]
+    fn temperature(&self) -> zbus::Result<u32>;
+    #[zbus(property, name = "Temperature")]
+    fn set_temperature(&self, kelvin: u32) -> zbus::Result<()>;
+
+    /// One step warmer or cooler, also turns night light on. Returns the new temperature.
+    #[zbus(name = "StepTemperature")]
+    fn step_temperature(&self, warmer: bool) -> zbus::Result<u32>;
+
+    /// Next intensity preset, also turns night light on. Returns the new level.
+    #[zbus(name = "CycleLevel")]
+    fn cycle_level(&self) -> zbus::Result<u8>;
+
+    /// Fade duration in milliseconds.
+    #[zbus(property, name = "TransitionDuration")]
+    fn transition_duration(&self) -> zbus::Result<u32>;
+    #[zbus(property, name = "TransitionDuration")]
+    fn set_transition_duration(&self, millis: u32) -> zbus::Result<()>;
+
+    /// `disabled`, `custom` or `solar`.
+    #[zbus(property, name = "ScheduleMode")]
+    fn schedule_mode(&self) -> zbus::Result<String>;
+    #[zbus(property, name = "ScheduleMode")]
+    fn set_schedule_mode(&self, mode: &str) -> zbus::Result<()>;
+
+    /// Start of the custom schedule, in minutes since midnight.
+    #[zbus(property, name = "ScheduleFrom")]
+    fn schedule_from(&self) -> zbus::Result<u16>;
+    #[zbus(property, name = "ScheduleFrom")]
+    fn set_schedule_from(&self, minute: u16) -> zbus::Result<()>;
+
+    /// End of the custom schedule, in minutes since midnight.
+    #[zbus(property, name = "ScheduleTo")]
+    fn schedule_to(&self) -> zbus::Result<u16>;
+    #[zbus(property, name = "ScheduleTo")]
+    fn set_schedule_to(&self, minute: u16) -> zbus::Result<()>;
+
+    /// Minutes night light takes to fade in or out after a schedule transition.
+    #[zbus(property, name = "ScheduleRamp")]
+    fn schedule_ramp(&self) -> zbus::Result<u16>;
+    #[zbus(property, name = "ScheduleRamp")]
+    fn set_schedule_ramp(&self, minutes: u16) -> zbus::Result<()>;
+
+    /// Latitude and longitude for the solar schedule, an error if none is set.
+    #[zbus(property, name = "Location")]
+    fn location(&self) -> zbus::Result<(f64, f64)>;
+    #[zbus(property, name = "Location")]
+    fn set_location(&self, location: (f64, f64)) -> zbus::Result<()>;
+
+    #[zbus(property, name = "TintCaptures")]
+    fn tint_captures(&self) -> zbus::Result<Vec<String>>;
+    #[zbus(property, name = "TintCaptures")]
+    fn set_tint_captures(&self, sources: Vec<String>) -> zbus::Result<()>;
+
+    #[zbus(property, name = "PreserveLuminance")]
+    fn preserve_luminance(&self) -> zbus::Result<bool>;
+    #[zbus(property, name = "PreserveLuminance")]
+    fn set_preserve_luminance(&self, preserve_luminance: bool) -> zbus::Result<()>;
+
+    /// Share of the light taken away while night light is active, `0.0` to `0.8`.
+    #[zbus(property, name = "Dim")]
+    fn dim(&self) -> zbus::Result<f64>;
+    #[zbus(property, name = "Dim")]
+    fn set_dim(&self, dim: f64) -> zbus::Result<()>;
+
+    #[zbus(property, name = "PauseForContent")]
+    fn pause_for_content(&self) -> zbus::Result<Vec<String>>;
+    #[zbus(property, name = "PauseForContent")]
+    fn set_pause_for_content(&self, kinds: Vec<String>) -> zbus::Result<()>;
+
+    /// Paused outputs by connector, with the content that paused them.
+    #[zbus(property, name = "SuspendReasons")]
+    fn suspend_reasons(&self) -> zbus::Result<std::collections::HashMap<String, String>>;
+
+    #[zbus(property, name = "Inhibited")]
+    fn inhibited(&self) -> zbus::Result<bool>;
+
+    /// `enabled`, `disabled` or empty if the schedule is in control.
+    #[zbus(property, name = "Override")]
+    fn override_(&self) -> zbus::Result<String>;
+
+    /// What the schedule asks for, `enabled`, `disabled` or empty without an active schedule.
+    #[zbus(property, name = "Scheduled")]
+    fn scheduled(&self) -> zbus::Result<String>;
+
+    /// Unix timestamp the override ends at, `0` if it ends with the next transition.
+    #[zbus(property, name = "OverrideExpiry")]
+    fn override_expiry(&self) -> zbus::Result<i64>;
+
+    /// Keeps night light off until `Uninhibit` or until this connection closes.
+    #[zbus(name = "Inhibit")]
+    fn inhibit(&self, reason: &str) -> zbus::Result<u32>;
+
+    #[zbus(name = "Uninhibit")]
+    fn uninhibit(&self, cookie: u32) -> zbus::Result<()>;
+
+    #[zbus(name = "DisableUntil")]
+    fn disable_until(&self, until: i64) -> zbus::Result<()>;
+
+    #[zbus(name = "EnableFor")]
+    fn enable_for(&self, seconds: u32) -> zbus::Result<()>;
+
+    #[zbus(name = "DisableUntilNextTransition")]
+    fn disable_until_next_transition(&self) -> zbus::Result<()>;
+
+    #[zbus(name = "GetOutputs")]
+    fn get_outputs(&self) -> zbus::Result<Vec<OutputInfo>>;
+
+    #[zbus(name = "SetOutputTemperature")]
+    fn set_output_temperature(&self, connector: &str, kelvin: u32) -> zbus::Result<()>;
+
+    #[zbus(name = "SetOutputEnabled")]
+    fn set_output_enabled(&self, connector: &str, enabled: bool) -> zbus::Result<()>;
+
+    #[zbus(name = "ResetOutput")]
+    fn reset_output(&self, connector: &str) -> zbus::Result<()>;
+}